@@ -1,79 +1,80 @@
+use crate::error::{Error, Result};
 use cpal::traits::{DeviceTrait, HostTrait};
 
-#[derive(Debug)]
-struct Opt {
-    #[cfg(all(
-        any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
-        feature = "jack"
-    ))]
-    jack: bool,
-
-    device: String,
-}
-
-impl Opt {
-    fn from_args() -> Self {
-        let app = clap::App::new("beep").arg_from_usage("[DEVICE] 'The audio device to use'");
-        #[cfg(all(
-            any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
-            feature = "jack"
-        ))]
-        let app = app.arg_from_usage("-j, --jack 'Use the JACK host");
-        let matches = app.get_matches();
-        let device = matches.value_of("DEVICE").unwrap_or("pulse").to_string();
-
-        #[cfg(all(
-            any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
-            feature = "jack"
-        ))]
-        return Opt {
-            jack: matches.is_present("jack"),
-            device,
-        };
-
-        #[cfg(any(
-            not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd")),
-            not(feature = "jack")
-        ))]
-        Opt { device }
-    }
+pub struct OutputDevice {
+    pub name: String,
+    pub is_default: bool,
+    pub configs: Vec<cpal::SupportedStreamConfigRange>,
 }
 
-pub fn configure_device() -> anyhow::Result<cpal::Device> {
-    let opt = Opt::from_args();
-
+pub fn select_host(jack: bool) -> Result<cpal::Host> {
     // Conditionally compile with jack if the feature is specified.
     #[cfg(all(
         any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
         feature = "jack"
     ))]
-    // Manually check for flags. Can be passed through cargo with -- e.g.
-    // cargo run --release --example beep --features jack -- --jack
-    let host = if opt.jack {
-        cpal::host_from_id(cpal::available_hosts()
+    if jack {
+        let jack = cpal::available_hosts()
             .into_iter()
             .find(|id| *id == cpal::HostId::Jack)
-            .expect(
-                "make sure --features jack is specified. only works on OSes where jack is available",
-            )).expect("jack host unavailable")
-    } else {
-        cpal::default_host()
-    };
+            .ok_or_else(|| {
+                Error::Device(
+                    "make sure --features jack is specified. only works on OSes where jack is available"
+                        .to_string(),
+                )
+            })?;
+        return cpal::host_from_id(jack).map_err(|e| Error::Device(e.to_string()));
+    }
 
     #[cfg(any(
         not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd")),
         not(feature = "jack")
     ))]
-    let host = cpal::default_host();
+    if jack {
+        return Err(Error::Device(
+            "rynth was built without the jack feature".to_string(),
+        ));
+    }
+
+    Ok(cpal::default_host())
+}
 
-    let device = if opt.device == "default" {
+pub fn configure_device(host: &cpal::Host, name: &str) -> Result<cpal::Device> {
+    let device = if name == "default" {
         host.default_output_device()
     } else {
-        host.output_devices()?
-            .find(|x| x.name().map(|y| y == opt.device).unwrap_or(false))
+        host.output_devices()
+            .map_err(|e| Error::Device(e.to_string()))?
+            .find(|x| x.name().map(|y| y == name).unwrap_or(false))
     }
-    .expect("failed to find output device");
+    .ok_or_else(|| Error::Device(format!("failed to find output device {}", name)))?;
 
-    println!("Output device: {}", device.name()?);
+    println!(
+        "Output device: {}",
+        device.name().map_err(|e| Error::Device(e.to_string()))?
+    );
     Ok(device)
 }
+
+// Devices that can't report their configurations are listed without any.
+pub fn list_output_devices(host: &cpal::Host) -> Result<Vec<OutputDevice>> {
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
+
+    let mut devices = vec![];
+    for device in host
+        .output_devices()
+        .map_err(|e| Error::Device(e.to_string()))?
+    {
+        let name = device.name().map_err(|e| Error::Device(e.to_string()))?;
+        let configs = device
+            .supported_output_configs()
+            .map(|configs| configs.collect())
+            .unwrap_or_default();
+        devices.push(OutputDevice {
+            is_default: default_name.as_ref() == Some(&name),
+            name,
+            configs,
+        });
+    }
+    Ok(devices)
+}
@@ -0,0 +1,295 @@
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{
+    BufferSize, BuildStreamError, FrameCount, SampleFormat, StreamConfig, SupportedBufferSize,
+    SupportedStreamConfigRange,
+};
+
+use crate::app::backends::{AudioBackend, AudioCallback, DeviceSample, SampleConverter};
+use crate::core::{EngineSpec, SamplingRate};
+use crate::error::{Error, Result};
+
+pub struct CpalBackend {
+    pub device: cpal::Device,
+    // Dithers when the device only takes integer samples.
+    pub dither: bool,
+}
+
+impl CpalBackend {
+    pub fn new(device: cpal::Device) -> Self {
+        Self {
+            device,
+            dither: false,
+        }
+    }
+
+    fn supported_configs(&self) -> Vec<ConfigCandidate> {
+        match self.device.supported_output_configs() {
+            Ok(configs) => configs.map(|c| ConfigCandidate::from(&c)).collect(),
+            Err(_) => vec![],
+        }
+    }
+}
+
+fn device_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::Device(e.to_string())
+}
+
+// What matters about a supported config when choosing one.
+#[derive(Clone, PartialEq, Debug)]
+struct ConfigCandidate {
+    channels: u16,
+    min_sampling_rate: u32,
+    max_sampling_rate: u32,
+    format: SampleFormat,
+    buffer_size: SupportedBufferSize,
+}
+
+impl From<&SupportedStreamConfigRange> for ConfigCandidate {
+    fn from(config: &SupportedStreamConfigRange) -> Self {
+        Self {
+            channels: config.channels(),
+            min_sampling_rate: config.min_sample_rate().0,
+            max_sampling_rate: config.max_sample_rate().0,
+            format: config.sample_format(),
+            buffer_size: config.buffer_size().clone(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct StreamChoice {
+    sampling_rate: u32,
+    format: SampleFormat,
+    buffer_size: Option<SupportedBufferSize>,
+}
+
+fn format_preference(format: SampleFormat) -> u8 {
+    match format {
+        SampleFormat::F32 => 2,
+        SampleFormat::I16 => 1,
+        SampleFormat::U16 => 0,
+    }
+}
+
+// Among the configs with the engine's channel count, prefers the requested sampling rate, then
+// the closest one, then the format that loses the least in conversion.
+fn choose_config(spec: &EngineSpec, candidates: &[ConfigCandidate]) -> Option<StreamChoice> {
+    let requested = spec.sampling_rate.0;
+    candidates
+        .iter()
+        .filter(|c| c.channels == spec.channels.0)
+        .map(|c| {
+            let sampling_rate = requested.clamp(c.min_sampling_rate, c.max_sampling_rate);
+            (c, sampling_rate)
+        })
+        .max_by_key(|(c, sampling_rate)| {
+            (
+                std::cmp::Reverse((*sampling_rate as i64 - requested as i64).abs()),
+                format_preference(c.format),
+            )
+        })
+        .map(|(c, sampling_rate)| StreamChoice {
+            sampling_rate,
+            format: c.format,
+            buffer_size: Some(c.buffer_size.clone()),
+        })
+}
+
+// Asks for max_samples_per_step, clamped to what the device reports for the chosen config.
+fn fixed_buffer_size(spec: &EngineSpec, supported: Option<&SupportedBufferSize>) -> BufferSize {
+    let requested = spec.max_samples_per_step as FrameCount;
+    match supported {
+        Some(SupportedBufferSize::Range { min, max }) => {
+            BufferSize::Fixed(requested.clamp(*min, (*max).max(*min)))
+        }
+        _ => BufferSize::Fixed(requested),
+    }
+}
+
+type SharedCallback = Arc<Mutex<AudioCallback>>;
+
+fn build_stream<T: DeviceSample + cpal::Sample>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    callback: SharedCallback,
+    mut converter: SampleConverter,
+) -> std::result::Result<cpal::Stream, BuildStreamError> {
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            // Only the stream that was built ever locks the callback, the Mutex just lets the
+            // fallback build reuse it. Should the lock still fail, the device gets silence
+            // rather than whatever was left in its buffer.
+            match callback.try_lock() {
+                Ok(mut callback) => converter.process(data, &mut **callback),
+                Err(_) => data.fill(T::from_f32(0.0, 0.0)),
+            }
+        },
+        |err| eprintln!("an error occurred on stream: {}", err),
+    )
+}
+
+impl AudioBackend for CpalBackend {
+    fn preferred_sampling_rate(&self, spec: &EngineSpec) -> SamplingRate {
+        match choose_config(spec, &self.supported_configs()) {
+            Some(choice) => SamplingRate(choice.sampling_rate),
+            None => spec.sampling_rate,
+        }
+    }
+
+    fn run(
+        &mut self,
+        spec: &EngineSpec,
+        callback: AudioCallback,
+        stop: Receiver<()>,
+    ) -> Result<()> {
+        // Devices that can't list their configs get what the engine produces.
+        let choice = choose_config(spec, &self.supported_configs()).unwrap_or(StreamChoice {
+            sampling_rate: spec.sampling_rate.0,
+            format: SampleFormat::F32,
+            buffer_size: None,
+        });
+        if choice.sampling_rate != spec.sampling_rate.0 {
+            return Err(Error::Device(format!(
+                "the device doesn't support {} channels at {} Hz, the closest supported rate is {} Hz",
+                spec.channels.0, spec.sampling_rate.0, choice.sampling_rate
+            )));
+        }
+
+        let mut config = StreamConfig {
+            channels: spec.channels.0,
+            sample_rate: cpal::SampleRate(spec.sampling_rate.0),
+            buffer_size: fixed_buffer_size(spec, choice.buffer_size.as_ref()),
+        };
+
+        // The callback is shared so it's still around for the second attempt when the device
+        // rejects the fixed buffer size. The engine handles whatever size it ends up with.
+        let callback = Arc::new(Mutex::new(callback));
+        let build = |config: &StreamConfig| {
+            let converter = SampleConverter::new(spec, self.dither);
+            match choice.format {
+                SampleFormat::F32 => {
+                    build_stream::<f32>(&self.device, config, callback.clone(), converter)
+                }
+                SampleFormat::I16 => {
+                    build_stream::<i16>(&self.device, config, callback.clone(), converter)
+                }
+                SampleFormat::U16 => {
+                    build_stream::<u16>(&self.device, config, callback.clone(), converter)
+                }
+            }
+        };
+        let stream = match build(&config) {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!(
+                    "falling back to the default buffer size, {:?} was rejected: {}",
+                    config.buffer_size, e
+                );
+                config.buffer_size = BufferSize::Default;
+                build(&config).map_err(device_error)?
+            }
+        };
+
+        thread::sleep(Duration::from_millis(100));
+
+        stream.play().map_err(device_error)?;
+
+        // Blocks until the sender hangs up.
+        while stop.recv().is_ok() {}
+
+        stream.pause().map_err(device_error)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Channels, ModulationRate};
+
+    fn spec() -> EngineSpec {
+        EngineSpec::new(SamplingRate(48000), ModulationRate(100), Channels(2), 128)
+    }
+
+    fn candidate(channels: u16, rates: (u32, u32), format: SampleFormat) -> ConfigCandidate {
+        ConfigCandidate {
+            channels,
+            min_sampling_rate: rates.0,
+            max_sampling_rate: rates.1,
+            format,
+            buffer_size: SupportedBufferSize::Unknown,
+        }
+    }
+
+    #[test]
+    fn clamps_buffer_size_to_supported_range() {
+        let range = |min, max| SupportedBufferSize::Range { min, max };
+
+        assert_eq!(
+            fixed_buffer_size(&spec(), Some(&range(64, 4096))),
+            BufferSize::Fixed(128)
+        );
+        assert_eq!(
+            fixed_buffer_size(&spec(), Some(&range(256, 4096))),
+            BufferSize::Fixed(256)
+        );
+        assert_eq!(
+            fixed_buffer_size(&spec(), Some(&range(16, 64))),
+            BufferSize::Fixed(64)
+        );
+        assert_eq!(
+            fixed_buffer_size(&spec(), Some(&SupportedBufferSize::Unknown)),
+            BufferSize::Fixed(128)
+        );
+        assert_eq!(fixed_buffer_size(&spec(), None), BufferSize::Fixed(128));
+    }
+
+    #[test]
+    fn prefers_float_formats_at_the_requested_rate() {
+        let candidates = [
+            candidate(2, (44100, 48000), SampleFormat::U16),
+            candidate(2, (44100, 48000), SampleFormat::I16),
+            candidate(1, (44100, 48000), SampleFormat::F32),
+        ];
+        assert_eq!(
+            choose_config(&spec(), &candidates),
+            Some(StreamChoice {
+                sampling_rate: 48000,
+                format: SampleFormat::I16,
+                buffer_size: Some(SupportedBufferSize::Unknown),
+            })
+        );
+
+        let candidates = [
+            candidate(2, (44100, 48000), SampleFormat::I16),
+            candidate(2, (44100, 96000), SampleFormat::F32),
+        ];
+        assert_eq!(
+            choose_config(&spec(), &candidates).map(|c| c.format),
+            Some(SampleFormat::F32)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_closest_sampling_rate() {
+        let candidates = [
+            candidate(2, (22050, 22050), SampleFormat::F32),
+            candidate(2, (44100, 44100), SampleFormat::U16),
+        ];
+        let choice = choose_config(&spec(), &candidates).unwrap();
+        assert_eq!(choice.sampling_rate, 44100);
+        assert_eq!(choice.format, SampleFormat::U16);
+
+        assert_eq!(
+            choose_config(&spec(), &[candidate(6, (48000, 48000), SampleFormat::F32)]),
+            None
+        );
+    }
+}
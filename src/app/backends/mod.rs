@@ -0,0 +1,78 @@
+mod cpal_backend;
+mod null_backend;
+mod raw_backend;
+mod sample_conversion;
+mod wav_backend;
+
+pub use cpal_backend::*;
+pub use null_backend::*;
+pub use raw_backend::*;
+pub use sample_conversion::*;
+pub use wav_backend::*;
+
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::core::{EngineSpec, SamplingRate};
+use crate::error::Result;
+use crate::render::duration_in_samples;
+
+pub type AudioCallback = Box<dyn FnMut(&mut [f32]) + Send>;
+
+pub trait AudioBackend: Send {
+    // The sampling rate to run at when the user didn't ask for one, e.g. the closest one a device
+    // supports.
+    fn preferred_sampling_rate(&self, spec: &EngineSpec) -> SamplingRate {
+        spec.sampling_rate
+    }
+
+    // Pulls interleaved audio from the callback until the stop sender hangs up.
+    fn run(&mut self, spec: &EngineSpec, callback: AudioCallback, stop: Receiver<()>)
+        -> Result<()>;
+}
+
+// Drives the callback one block at a time for backends without a device clock. Paced backends
+// keep to the sampling rate, measured from the start so sleeping late doesn't accumulate. With a
+// length, the sink gets exactly that many frames, the last block cut short if need be.
+fn run_blocks<F>(
+    spec: &EngineSpec,
+    mut callback: AudioCallback,
+    stop: Receiver<()>,
+    paced: bool,
+    length: Option<Duration>,
+    mut sink: F,
+) -> Result<()>
+where
+    F: FnMut(&[f32]) -> Result<bool>,
+{
+    let channels = spec.channels.0 as usize;
+    let mut buffer = vec![0.0; spec.max_samples_per_step * channels];
+    let total_samples = length.map(|length| duration_in_samples(length, spec.sampling_rate));
+    let start = Instant::now();
+    let mut rendered_samples = 0;
+
+    while let Err(TryRecvError::Empty) | Ok(()) = stop.try_recv() {
+        let remaining = total_samples.map_or(u64::MAX, |total| total - rendered_samples);
+        if remaining == 0 {
+            break;
+        }
+
+        callback(&mut buffer);
+        let samples = remaining.min(spec.max_samples_per_step as u64);
+        if !sink(&buffer[..samples as usize * channels])? {
+            break;
+        }
+
+        rendered_samples += samples;
+        if paced {
+            let due =
+                Duration::from_secs_f64(rendered_samples as f64 / spec.sampling_rate.0 as f64);
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::sync::mpsc::Receiver;
+
+use crate::app::backends::{run_blocks, AudioBackend, AudioCallback};
+use crate::core::EngineSpec;
+use crate::error::Result;
+
+// Renders in real time and throws the output away, like a device nobody listens to.
+#[derive(Default)]
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn run(
+        &mut self,
+        spec: &EngineSpec,
+        callback: AudioCallback,
+        stop: Receiver<()>,
+    ) -> Result<()> {
+        run_blocks(spec, callback, stop, true, None, |_| Ok(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Channels, ModulationRate, SamplingRate};
+    use std::sync::mpsc::channel;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn keeps_real_time_pace() {
+        // Three blocks of 50 ms each.
+        let spec = EngineSpec::new(SamplingRate(1000), ModulationRate(100), Channels(1), 50);
+        let (stop, stopped) = channel();
+        let mut stop = Some(stop);
+        let mut calls = 0;
+        let callback = Box::new(move |_: &mut [f32]| {
+            calls += 1;
+            if calls == 3 {
+                stop.take();
+            }
+        });
+
+        let start = Instant::now();
+        NullBackend.run(&spec, callback, stopped).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+}
@@ -0,0 +1,106 @@
+use std::io::{ErrorKind, Write};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use crate::app::backends::{run_blocks, AudioBackend, AudioCallback};
+use crate::core::EngineSpec;
+use crate::error::{Error, Result};
+
+// Writes interleaved 32 bit float little endian samples, e.g. to stdout for
+// `rynth play ... | aplay -f FLOAT_LE -c 2 -r 48000`. Pipes push back on their own, so pacing is
+// off by default, files need it. Playback ends when the reader goes away or after `length`.
+pub struct RawPcmBackend<W: Write + Send> {
+    pub writer: W,
+    pub paced: bool,
+    pub length: Option<Duration>,
+    bytes: Vec<u8>,
+}
+
+impl<W: Write + Send> RawPcmBackend<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            paced: false,
+            length: None,
+            bytes: vec![],
+        }
+    }
+}
+
+impl<W: Write + Send> AudioBackend for RawPcmBackend<W> {
+    fn run(
+        &mut self,
+        spec: &EngineSpec,
+        callback: AudioCallback,
+        stop: Receiver<()>,
+    ) -> Result<()> {
+        let writer = &mut self.writer;
+        let bytes = &mut self.bytes;
+        bytes.reserve(spec.max_samples_per_step * spec.channels.0 as usize * 4);
+
+        run_blocks(spec, callback, stop, self.paced, self.length, |samples| {
+            bytes.clear();
+            for s in samples {
+                bytes.extend_from_slice(&s.to_le_bytes());
+            }
+            match writer.write_all(bytes) {
+                Ok(()) => Ok(true),
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(false),
+                Err(e) => Err(Error::Io(e.to_string())),
+            }
+        })?;
+
+        match self.writer.flush() {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(Error::Io(e.to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Channels, ModulationRate, SamplingRate};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn writes_little_endian_floats_until_stopped() {
+        let spec = EngineSpec::new(SamplingRate(1000), ModulationRate(100), Channels(2), 4);
+        let (stop, stopped) = channel();
+        let mut stop = Some(stop);
+        let mut calls = 0;
+        let callback = Box::new(move |data: &mut [f32]| {
+            calls += 1;
+            data.fill(calls as f32);
+            if calls == 3 {
+                stop.take();
+            }
+        });
+
+        let mut backend = RawPcmBackend::new(vec![]);
+        backend.run(&spec, callback, stopped).unwrap();
+
+        let samples: Vec<f32> = backend
+            .writer
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let expected: Vec<f32> = (1..=3).flat_map(|n| vec![n as f32; 8]).collect();
+        assert_eq!(samples, expected);
+    }
+
+    #[test]
+    fn writes_exactly_the_given_length() {
+        let spec = EngineSpec::new(SamplingRate(1000), ModulationRate(100), Channels(2), 4);
+        let (_stop, stopped) = channel();
+
+        let mut backend = RawPcmBackend::new(vec![]);
+        backend.length = Some(Duration::from_millis(10));
+        backend
+            .run(&spec, Box::new(|data: &mut [f32]| data.fill(0.5)), stopped)
+            .unwrap();
+
+        // Ten frames of two channels, four bytes each.
+        assert_eq!(backend.writer.len(), 10 * 2 * 4);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::core::EngineSpec;
+
+// A sample type a device can be fed with, converted from the engine's f32 output.
+pub trait DeviceSample: Copy + Send + 'static {
+    // Only integer formats get dithered.
+    const QUANTIZED: bool;
+
+    // Clips to [-1, 1]. The dither is in units of the least significant bit.
+    fn from_f32(sample: f32, dither: f32) -> Self;
+}
+
+impl DeviceSample for f32 {
+    const QUANTIZED: bool = false;
+
+    fn from_f32(sample: f32, _: f32) -> Self {
+        sample
+    }
+}
+
+impl DeviceSample for i16 {
+    const QUANTIZED: bool = true;
+
+    // Scaled by 32767 so both ends clip symmetrically.
+    fn from_f32(sample: f32, dither: f32) -> Self {
+        (sample * 32767.0 + dither).round().clamp(-32767.0, 32767.0) as i16
+    }
+}
+
+impl DeviceSample for u16 {
+    const QUANTIZED: bool = true;
+
+    fn from_f32(sample: f32, dither: f32) -> Self {
+        (i16::from_f32(sample, dither) as i32 + 32768) as u16
+    }
+}
+
+// Triangular noise of up to one least significant bit either way, from a xorshift generator so
+// it neither allocates nor locks.
+struct Dither {
+    state: u32,
+}
+
+impl Dither {
+    fn new() -> Self {
+        Self { state: 0x9e37_79b9 }
+    }
+
+    fn next_uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn next(&mut self) -> f32 {
+        self.next_uniform() - self.next_uniform()
+    }
+}
+
+// Renders into a buffer allocated up front and converts from it, so device buffers of any size
+// and format are filled without allocating on the audio thread.
+pub struct SampleConverter {
+    scratch: Vec<f32>,
+    dither: Option<Dither>,
+}
+
+impl SampleConverter {
+    pub fn new(spec: &EngineSpec, dither: bool) -> Self {
+        Self {
+            scratch: vec![0.0; spec.max_samples_per_step * spec.channels.0 as usize],
+            dither: if dither { Some(Dither::new()) } else { None },
+        }
+    }
+
+    pub fn process<T: DeviceSample>(
+        &mut self,
+        output: &mut [T],
+        render: &mut dyn FnMut(&mut [f32]),
+    ) {
+        // The scratch buffer holds whole frames, so every chunk does too.
+        for chunk in output.chunks_mut(self.scratch.len()) {
+            let scratch = &mut self.scratch[..chunk.len()];
+            render(scratch);
+
+            for (output_sample, sample) in chunk.iter_mut().zip(scratch.iter()) {
+                let dither = match self.dither.as_mut() {
+                    Some(dither) if T::QUANTIZED => dither.next(),
+                    _ => 0.0,
+                };
+                *output_sample = T::from_f32(*sample, dither);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Channels, ModulationRate, SamplingRate};
+
+    fn spec(max_samples_per_step: usize) -> EngineSpec {
+        EngineSpec::new(
+            SamplingRate(48000),
+            ModulationRate(100),
+            Channels(2),
+            max_samples_per_step,
+        )
+    }
+
+    #[test]
+    fn scales_and_clips_integer_samples() {
+        let samples = [0.0, 0.5, -0.5, 1.0, -1.0, 1.5, -1.5];
+
+        let converted: Vec<i16> = samples.iter().map(|s| i16::from_f32(*s, 0.0)).collect();
+        assert_eq!(
+            converted,
+            vec![0, 16384, -16384, 32767, -32767, 32767, -32767]
+        );
+
+        let converted: Vec<u16> = samples.iter().map(|s| u16::from_f32(*s, 0.0)).collect();
+        assert_eq!(converted, vec![32768, 49152, 16384, 65535, 1, 65535, 1]);
+
+        assert_eq!(f32::from_f32(0.25, 0.5), 0.25);
+    }
+
+    #[test]
+    fn renders_buffers_larger_than_the_scratch_buffer() {
+        let mut converter = SampleConverter::new(&spec(4), false);
+        let mut rendered = 0;
+        let mut render_sizes = vec![];
+        let mut render = |buffer: &mut [f32]| {
+            render_sizes.push(buffer.len());
+            for sample in buffer.iter_mut() {
+                *sample = rendered as f32 / 32767.0;
+                rendered += 1;
+            }
+        };
+
+        let mut output = [0i16; 20];
+        converter.process(&mut output, &mut render);
+
+        assert_eq!(render_sizes, vec![8, 8, 4]);
+        assert_eq!(output.to_vec(), (0..20).collect::<Vec<i16>>());
+    }
+
+    #[test]
+    fn dithers_integer_samples_within_one_step() {
+        let mut converter = SampleConverter::new(&spec(128), true);
+        let mut output = [0i16; 256];
+        converter.process(&mut output, &mut |buffer| buffer.fill(0.25));
+
+        let undithered = i16::from_f32(0.25, 0.0);
+        assert!(output.iter().all(|s| (s - undithered).abs() <= 1));
+        assert!(output.iter().any(|s| *s != undithered));
+
+        let mut output = [0.0f32; 256];
+        converter.process(&mut output, &mut |buffer| buffer.fill(0.25));
+        assert_eq!(output.to_vec(), vec![0.25; 256]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use crate::app::backends::{run_blocks, AudioBackend, AudioCallback};
+use crate::core::EngineSpec;
+use crate::error::Result;
+
+// Records what would have been played into a 32 bit float WAV file.
+pub struct WavBackend {
+    pub path: PathBuf,
+    // Without pacing the file grows as fast as the engine renders.
+    pub paced: bool,
+    // Stops by itself once the file is this long.
+    pub length: Option<Duration>,
+}
+
+impl WavBackend {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            paced: true,
+            length: None,
+        }
+    }
+}
+
+impl AudioBackend for WavBackend {
+    fn run(
+        &mut self,
+        spec: &EngineSpec,
+        callback: AudioCallback,
+        stop: Receiver<()>,
+    ) -> Result<()> {
+        let wav_spec = hound::WavSpec {
+            channels: spec.channels.0,
+            sample_rate: spec.sampling_rate.0,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(&self.path, wav_spec)?;
+
+        run_blocks(spec, callback, stop, self.paced, self.length, |samples| {
+            for s in samples {
+                writer.write_sample(*s)?;
+            }
+            Ok(true)
+        })?;
+        writer.finalize()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Channels, ModulationRate, SamplingRate};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn finalizes_the_file_when_stopped() {
+        let spec = EngineSpec::new(SamplingRate(1000), ModulationRate(100), Channels(2), 4);
+        let (stop, stopped) = channel();
+        let mut stop = Some(stop);
+        let mut calls = 0;
+        let callback = Box::new(move |data: &mut [f32]| {
+            calls += 1;
+            data.fill(calls as f32);
+            if calls == 3 {
+                stop.take();
+            }
+        });
+        let path =
+            std::env::temp_dir().join(format!("rynth-wav-backend-{}.wav", std::process::id()));
+
+        let mut backend = WavBackend {
+            path: path.clone(),
+            paced: false,
+            length: None,
+        };
+        backend.run(&spec, callback, stopped).unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let samples: Vec<f32> = reader.samples().map(|s| s.unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 1000);
+        let expected: Vec<f32> = (1..=3).flat_map(|n| vec![n as f32; 8]).collect();
+        assert_eq!(samples, expected);
+    }
+
+    #[test]
+    fn stops_after_the_given_length() {
+        let spec = EngineSpec::new(SamplingRate(1000), ModulationRate(100), Channels(2), 4);
+        let (_stop, stopped) = channel();
+        let path = std::env::temp_dir().join(format!(
+            "rynth-wav-backend-length-{}.wav",
+            std::process::id()
+        ));
+
+        let mut backend = WavBackend {
+            path: path.clone(),
+            paced: false,
+            length: Some(Duration::from_millis(10)),
+        };
+        backend
+            .run(&spec, Box::new(|data: &mut [f32]| data.fill(0.5)), stopped)
+            .unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        let frames = reader.duration();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(frames, 10);
+    }
+}
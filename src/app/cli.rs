@@ -0,0 +1,428 @@
+use std::ffi::OsString;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use crate::core::{CHANNELS, SAMPLING_RATES, STEP_SIZES};
+use crate::patch::Patch;
+use crate::render::WavFormat;
+
+// Overrides of the engine section of a patch.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct EngineOverrides {
+    pub sampling_rate: Option<u32>,
+    pub buffer_size: Option<usize>,
+    pub channels: Option<u16>,
+}
+
+impl EngineOverrides {
+    pub fn apply(&self, patch: &mut Patch) {
+        if let Some(sampling_rate) = self.sampling_rate {
+            patch.engine.sampling_rate = sampling_rate;
+        }
+        if let Some(buffer_size) = self.buffer_size {
+            patch.engine.max_samples_per_step = buffer_size;
+        }
+        if let Some(channels) = self.channels {
+            patch.engine.channels = channels;
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum BackendChoice {
+    Cpal,
+    Null,
+    Wav(PathBuf),
+    // Standard output when no file is given.
+    Raw(Option<PathBuf>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CliCommand {
+    ListDevices {
+        jack: bool,
+    },
+    Play {
+        jack: bool,
+        device: String,
+        patch: PathBuf,
+        engine: EngineOverrides,
+        // Plays until interrupted when missing.
+        duration: Option<Duration>,
+        backend: BackendChoice,
+        // Only applies to devices that take integer samples.
+        dither: bool,
+    },
+    Render {
+        patch: PathBuf,
+        output: PathBuf,
+        engine: EngineOverrides,
+        duration: Duration,
+        format: WavFormat,
+    },
+}
+
+const DEFAULT_RENDER_SECONDS: &str = "10";
+
+fn engine_arguments<'a, 'b>(command: App<'a, 'b>) -> App<'a, 'b> {
+    command
+        .arg(
+            Arg::with_name("sample-rate")
+                .short("r")
+                .long("sample-rate")
+                .value_name("HZ")
+                .help("Overrides the sample rate of the patch"),
+        )
+        .arg(
+            Arg::with_name("buffer-size")
+                .short("b")
+                .long("buffer-size")
+                .value_name("FRAMES")
+                .help("Overrides the number of frames processed per step"),
+        )
+        .arg(
+            Arg::with_name("channels")
+                .short("c")
+                .long("channels")
+                .value_name("COUNT")
+                .help("Overrides the number of output channels"),
+        )
+}
+
+fn app<'a, 'b>() -> App<'a, 'b> {
+    let jack = Arg::with_name("jack")
+        .short("j")
+        .long("jack")
+        .help("Uses the JACK host");
+
+    App::new("rynth")
+        .about("A modular synthesizer")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("list-devices")
+                .about("Lists output devices and the configurations they support")
+                .arg(jack.clone()),
+        )
+        .subcommand(engine_arguments(
+            SubCommand::with_name("play")
+                .about("Plays a patch on an output device")
+                .arg(
+                    Arg::with_name("PATCH")
+                        .required(true)
+                        .help("The patch file"),
+                )
+                .arg(jack)
+                .arg(
+                    Arg::with_name("device")
+                        .short("d")
+                        .long("device")
+                        .value_name("NAME")
+                        .default_value("default")
+                        .help("The output device to use"),
+                )
+                .arg(
+                    Arg::with_name("duration")
+                        .short("t")
+                        .long("duration")
+                        .value_name("SECONDS")
+                        .help("Stops after this long instead of playing until Ctrl-C"),
+                )
+                .arg(
+                    Arg::with_name("backend")
+                        .long("backend")
+                        .possible_values(&["cpal", "null", "wav", "raw"])
+                        .default_value("cpal")
+                        .help("Where the audio goes, raw writes 32 bit float samples"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .required_if("backend", "wav")
+                        .help("The file written by the wav and raw backends"),
+                )
+                .arg(
+                    Arg::with_name("dither")
+                        .long("dither")
+                        .help("Dithers the output of devices that take integer samples"),
+                ),
+        ))
+        .subcommand(engine_arguments(
+            SubCommand::with_name("render")
+                .about("Renders a patch into a WAV file")
+                .arg(
+                    Arg::with_name("PATCH")
+                        .required(true)
+                        .help("The patch file"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("The WAV file to write"),
+                )
+                .arg(
+                    Arg::with_name("duration")
+                        .short("t")
+                        .long("duration")
+                        .value_name("SECONDS")
+                        .default_value(DEFAULT_RENDER_SECONDS),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["float32", "int16", "int24"])
+                        .default_value("float32"),
+                ),
+        ))
+}
+
+fn parse_value<T: std::str::FromStr>(matches: &ArgMatches, name: &str) -> clap::Result<Option<T>> {
+    match matches.value_of(name) {
+        Some(value) => value.parse().map(Some).map_err(|_| {
+            clap::Error::with_description(
+                &format!("invalid value \"{}\" for --{}", value, name),
+                clap::ErrorKind::InvalidValue,
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+fn parse_in_range<T>(
+    matches: &ArgMatches,
+    name: &str,
+    range: RangeInclusive<T>,
+) -> clap::Result<Option<T>>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    match parse_value(matches, name)? {
+        Some(value) if !range.contains(&value) => Err(clap::Error::with_description(
+            &format!(
+                "--{} has to be between {} and {}",
+                name,
+                range.start(),
+                range.end()
+            ),
+            clap::ErrorKind::InvalidValue,
+        )),
+        value => Ok(value),
+    }
+}
+
+fn parse_duration(matches: &ArgMatches) -> clap::Result<Option<Duration>> {
+    let seconds: Option<f64> = parse_value(matches, "duration")?;
+    match seconds.map(Duration::try_from_secs_f64) {
+        Some(Ok(duration)) if !duration.is_zero() => Ok(Some(duration)),
+        Some(_) => Err(clap::Error::with_description(
+            "--duration has to be a positive number of seconds",
+            clap::ErrorKind::InvalidValue,
+        )),
+        None => Ok(None),
+    }
+}
+
+fn parse_engine_overrides(matches: &ArgMatches) -> clap::Result<EngineOverrides> {
+    Ok(EngineOverrides {
+        sampling_rate: parse_in_range(matches, "sample-rate", SAMPLING_RATES)?,
+        buffer_size: parse_in_range(matches, "buffer-size", STEP_SIZES)?,
+        channels: parse_in_range(matches, "channels", CHANNELS)?,
+    })
+}
+
+// Requests for help come back as errors too, `clap::Error::exit` prints them.
+pub fn parse_args<I, T>(args: I) -> clap::Result<CliCommand>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = app().get_matches_from_safe(args)?;
+
+    match matches.subcommand() {
+        ("list-devices", Some(matches)) => Ok(CliCommand::ListDevices {
+            jack: matches.is_present("jack"),
+        }),
+        ("play", Some(matches)) => Ok(CliCommand::Play {
+            jack: matches.is_present("jack"),
+            device: matches.value_of("device").unwrap_or("default").to_string(),
+            patch: matches.value_of("PATCH").unwrap_or_default().into(),
+            engine: parse_engine_overrides(matches)?,
+            duration: parse_duration(matches)?,
+            backend: match matches.value_of("backend") {
+                Some("null") => BackendChoice::Null,
+                Some("wav") => {
+                    BackendChoice::Wav(matches.value_of("output").unwrap_or_default().into())
+                }
+                Some("raw") => BackendChoice::Raw(matches.value_of("output").map(PathBuf::from)),
+                _ => BackendChoice::Cpal,
+            },
+            dither: matches.is_present("dither"),
+        }),
+        ("render", Some(matches)) => Ok(CliCommand::Render {
+            patch: matches.value_of("PATCH").unwrap_or_default().into(),
+            output: matches.value_of("output").unwrap_or_default().into(),
+            engine: parse_engine_overrides(matches)?,
+            duration: parse_duration(matches)?.unwrap_or_default(),
+            format: match matches.value_of("format") {
+                Some("int16") => WavFormat::Int16,
+                Some("int24") => WavFormat::Int24,
+                _ => WavFormat::Float32,
+            },
+        }),
+        _ => Err(clap::Error::with_description(
+            "a subcommand is required",
+            clap::ErrorKind::MissingSubcommand,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_play_options() {
+        let command = parse_args([
+            "rynth",
+            "play",
+            "demo.toml",
+            "-d",
+            "pulse",
+            "-r",
+            "44100",
+            "-b",
+            "256",
+            "-c",
+            "1",
+            "-t",
+            "2.5",
+            "--dither",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            command,
+            CliCommand::Play {
+                jack: false,
+                device: "pulse".to_string(),
+                patch: "demo.toml".into(),
+                engine: EngineOverrides {
+                    sampling_rate: Some(44100),
+                    buffer_size: Some(256),
+                    channels: Some(1),
+                },
+                duration: Some(Duration::from_millis(2500)),
+                backend: BackendChoice::Cpal,
+                dither: true,
+            }
+        );
+        assert!(matches!(
+            parse_args(["rynth", "play", "demo.toml"]),
+            Ok(CliCommand::Play { duration: None, .. })
+        ));
+        assert!(matches!(
+            parse_args(["rynth", "play", "demo.toml", "--backend", "raw"]),
+            Ok(CliCommand::Play {
+                backend: BackendChoice::Raw(None),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_args([
+                "rynth",
+                "play",
+                "a",
+                "--backend",
+                "wav",
+                "-o",
+                "b",
+                "-t",
+                "1"
+            ]),
+            Ok(CliCommand::Play {
+                backend: BackendChoice::Wav(_),
+                duration: Some(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_args(["rynth", "play", "a", "--backend", "raw", "-o", "b"]),
+            Ok(CliCommand::Play {
+                backend: BackendChoice::Raw(Some(_)),
+                duration: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_render_options() {
+        let command = parse_args([
+            "rynth",
+            "render",
+            "demo.toml",
+            "-o",
+            "out.wav",
+            "--format",
+            "int24",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            command,
+            CliCommand::Render {
+                patch: "demo.toml".into(),
+                output: "out.wav".into(),
+                engine: EngineOverrides::default(),
+                duration: Duration::from_secs(10),
+                format: WavFormat::Int24,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(parse_args(["rynth", "render", "demo.toml"]).is_err());
+        assert!(parse_args(["rynth", "play", "demo.toml", "-r", "fast"]).is_err());
+        assert!(parse_args(["rynth", "play", "demo.toml", "-t", "-1"]).is_err());
+        assert!(parse_args(["rynth", "play", "demo.toml", "-t", "0"]).is_err());
+        assert!(parse_args(["rynth", "play", "demo.toml", "-t", "inf"]).is_err());
+        assert_eq!(
+            parse_args(["rynth", "play", "demo.toml", "-r", "0"])
+                .unwrap_err()
+                .kind,
+            clap::ErrorKind::InvalidValue
+        );
+        assert!(parse_args(["rynth", "play", "demo.toml", "-r", "10000000"]).is_err());
+        assert!(parse_args(["rynth", "play", "demo.toml", "-b", "0"]).is_err());
+        assert!(parse_args(["rynth", "play", "demo.toml", "-c", "0"]).is_err());
+        assert!(parse_args(["rynth", "render", "a", "-o", "b", "-c", "1000"]).is_err());
+        assert!(parse_args(["rynth", "render", "a", "-o", "b", "-t", "0"]).is_err());
+        assert!(parse_args(["rynth", "render", "a", "-o", "b", "-f", "mp3"]).is_err());
+        assert!(parse_args(["rynth"]).is_err());
+        assert!(parse_args(["rynth", "play", "demo.toml", "--backend", "wav"]).is_err());
+    }
+
+    #[test]
+    fn overrides_patch_engine_settings() {
+        let mut patch = Patch::from_toml(include_str!("../../patches/demo.toml")).unwrap();
+        EngineOverrides {
+            sampling_rate: Some(44100),
+            buffer_size: None,
+            channels: Some(1),
+        }
+        .apply(&mut patch);
+
+        assert_eq!(patch.engine.sampling_rate, 44100);
+        assert_eq!(patch.engine.max_samples_per_step, 128);
+        assert_eq!(patch.engine.channels, 1);
+    }
+}
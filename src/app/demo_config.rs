@@ -1,34 +1,53 @@
 use crate::components::{LowFrequencyOscillator, Oscillator};
-use crate::core::{empty_engine, AudioTopology, Channels, Engine, ModulationRate, SamplingRate};
+use crate::core::{
+    empty_engine, AudioTopology, Channels, Engine, ModulationCurve, ModulationMapping,
+    ModulationRate, Polarity, SamplingRate,
+};
+use crate::error::Result;
 
-pub fn create_demo_engine() -> (Engine, AudioTopology) {
+pub fn create_demo_engine() -> Result<(Engine, AudioTopology)> {
     let sampling_rate = SamplingRate(48000);
     let modulation_rate = ModulationRate(100);
     let samples_per_step = 128;
 
-    let (engine, mut topology) = empty_engine(
+    let (engine, _) = empty_engine(
         sampling_rate,
         modulation_rate,
         samples_per_step,
         Channels(2),
     );
+    let topology = create_demo_topology(&engine, 500.0)?;
+
+    Ok((engine, topology))
+}
+
+pub fn create_demo_topology(engine: &Engine, frequency: f32) -> Result<AudioTopology> {
+    let mut topology = engine.create_empty_topology();
 
     let modulator1_id = topology.add_modulator(LowFrequencyOscillator::new(
         2.0,
         engine.spec.modulation_rate,
-    ));
+    ))?;
     let modulator2_id = topology.add_modulator(LowFrequencyOscillator::new(
         10.0,
         engine.spec.modulation_rate,
-    ));
+    ))?;
 
-    let mut oscillator = Oscillator::new(500.0, engine.spec.sampling_rate);
+    let mut oscillator = Oscillator::new(frequency, engine.spec.sampling_rate);
 
     oscillator.level.set_value(0.3);
     oscillator.level.add_modulation(modulator1_id, 0.2);
-    oscillator.frequency.add_modulation(modulator2_id, 0.01); // 200Hz range
+    oscillator.frequency.add_mapped_modulation(
+        modulator2_id,
+        0.25, // a quarter octave up and down
+        ModulationMapping::octaves(Polarity::Bipolar, ModulationCurve::Linear),
+    )?;
+
+    let oscillator_id = topology.add_component(oscillator);
 
-    topology.add_component(oscillator);
+    topology.set_name(modulator1_id, "lfo1");
+    topology.set_name(modulator2_id, "lfo2");
+    topology.set_name(oscillator_id, "osc1");
 
-    (engine, topology)
+    Ok(topology)
 }
@@ -1,7 +1,11 @@
 pub mod audio_interface_configuration;
 pub mod audio_loop;
+pub mod backends;
+pub mod cli;
 pub mod demo_config;
 
 pub use audio_interface_configuration::*;
 pub use audio_loop::*;
+pub use backends::*;
+pub use cli::*;
 pub use demo_config::*;
@@ -0,0 +1,190 @@
+use crate::core::buffers::MultiChannelSliceMut;
+use crate::core::concepts::{AudioSampleIndex, SamplingRate};
+use crate::core::parameter::Parameter;
+use crate::core::reflection::{ParameterDescriptor, ParameterUnit, Parameterized};
+use crate::core::routing::AudioInputIndex;
+use crate::core::traits::{AudioComponent, AudioInputs};
+use crate::core::ModulationComponentsStore;
+use crate::error::Result;
+use std::ops::Range;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EnvelopeStage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+// A linear ADSR envelope applied to its input.
+pub struct Envelope {
+    pub attack: Parameter,
+    pub decay: Parameter,
+    pub sustain: Parameter,
+    pub release: Parameter,
+    stage: EnvelopeStage,
+    level: f32,
+    release_start_level: f32,
+    velocity: f32,
+    sampling_rate: SamplingRate,
+}
+
+impl Envelope {
+    pub fn new(sampling_rate: SamplingRate) -> Self {
+        Self {
+            attack: Parameter::new(0.01, 0.0, 10.0),
+            decay: Parameter::new(0.1, 0.0, 10.0),
+            sustain: Parameter::new(0.8, 0.0, 1.0),
+            release: Parameter::new(0.2, 0.0, 10.0),
+            stage: EnvelopeStage::Idle,
+            level: 0.0,
+            release_start_level: 0.0,
+            velocity: 1.0,
+            sampling_rate,
+        }
+    }
+
+    pub fn stage(&self) -> EnvelopeStage {
+        self.stage
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    // The amount a stage of the given length in seconds moves the level every sample.
+    fn step(&self, seconds: f32, distance: f32) -> f32 {
+        let samples = seconds * self.sampling_rate.0 as f32;
+        if samples < 1.0 {
+            distance
+        } else {
+            distance / samples
+        }
+    }
+
+    fn next_level(&mut self) -> f32 {
+        match self.stage {
+            EnvelopeStage::Idle => {}
+            EnvelopeStage::Attack => {
+                self.level += self.step(self.attack.final_value(), 1.0);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = EnvelopeStage::Decay;
+                }
+            }
+            EnvelopeStage::Decay => {
+                let sustain = self.sustain.final_value();
+                self.level -= self.step(self.decay.final_value(), 1.0 - sustain);
+                if self.level <= sustain {
+                    self.level = sustain;
+                    self.stage = EnvelopeStage::Sustain;
+                }
+            }
+            EnvelopeStage::Sustain => self.level = self.sustain.final_value(),
+            EnvelopeStage::Release => {
+                self.level -= self.step(self.release.final_value(), self.release_start_level);
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = EnvelopeStage::Idle;
+                }
+            }
+        }
+        self.level
+    }
+}
+
+const ENVELOPE_PARAMETERS: &[ParameterDescriptor] = &[
+    ParameterDescriptor {
+        name: "attack",
+        unit: ParameterUnit::Seconds,
+    },
+    ParameterDescriptor {
+        name: "decay",
+        unit: ParameterUnit::Seconds,
+    },
+    ParameterDescriptor {
+        name: "sustain",
+        unit: ParameterUnit::None,
+    },
+    ParameterDescriptor {
+        name: "release",
+        unit: ParameterUnit::Seconds,
+    },
+];
+
+impl Parameterized for Envelope {
+    fn type_name(&self) -> Option<&'static str> {
+        Some("envelope")
+    }
+
+    fn parameter_descriptors(&self) -> &'static [ParameterDescriptor] {
+        ENVELOPE_PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<&Parameter> {
+        match index {
+            0 => Some(&self.attack),
+            1 => Some(&self.decay),
+            2 => Some(&self.sustain),
+            3 => Some(&self.release),
+            _ => None,
+        }
+    }
+
+    fn parameter_mut(&mut self, index: usize) -> Option<&mut Parameter> {
+        match index {
+            0 => Some(&mut self.attack),
+            1 => Some(&mut self.decay),
+            2 => Some(&mut self.sustain),
+            3 => Some(&mut self.release),
+            _ => None,
+        }
+    }
+}
+
+impl AudioComponent for Envelope {
+    fn number_of_inputs(&self) -> usize {
+        1
+    }
+
+    fn process_audio(
+        &mut self,
+        inputs: &AudioInputs,
+        output: &mut MultiChannelSliceMut,
+        _: Range<AudioSampleIndex>,
+    ) {
+        let input = inputs.get(AudioInputIndex(0)).unwrap();
+        for (sample, input) in output.channel_mut(0).iter_mut().zip(input.channel(0)) {
+            *sample = *input * self.next_level() * self.velocity;
+        }
+    }
+
+    fn apply_modulations(
+        &mut self,
+        modulators: &ModulationComponentsStore,
+        sample: AudioSampleIndex,
+    ) -> Result<()> {
+        let attack = self.attack.apply_modulations(modulators, sample);
+        let decay = self.decay.apply_modulations(modulators, sample);
+        let sustain = self.sustain.apply_modulations(modulators, sample);
+        let release = self.release.apply_modulations(modulators, sample);
+        attack.and(decay).and(sustain).and(release)
+    }
+
+    fn note_on(&mut self, _note: u8, velocity: u8, _sample: AudioSampleIndex) {
+        self.velocity = velocity as f32 / 127.0;
+        self.stage = EnvelopeStage::Attack;
+    }
+
+    fn note_off(&mut self, _note: u8, _sample: AudioSampleIndex) {
+        if self.stage != EnvelopeStage::Idle {
+            self.release_start_level = self.level;
+            self.stage = EnvelopeStage::Release;
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.stage != EnvelopeStage::Idle
+    }
+}
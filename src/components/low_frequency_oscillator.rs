@@ -1,10 +1,16 @@
-use crate::core::concepts::{ModulationRate, ModulationSampleIndex};
+use crate::core::concepts::{AudioSampleIndex, ModulationRate, ModulationSampleIndex};
 use crate::core::parameter::Parameter;
+use crate::core::reflection::{ParameterDescriptor, ParameterUnit, Parameterized};
 use crate::core::traits::ModulationComponent;
+use crate::core::ModulationComponentsStore;
+use crate::error::Result;
 
 pub struct LowFrequencyOscillator {
     pub frequency: Parameter,
+    pub level: Parameter,
     pub current_level: f32,
+    phase_offset: f32,
+    current_frequency: f32,
     sample_rate: ModulationRate,
 }
 
@@ -12,20 +18,89 @@ impl LowFrequencyOscillator {
     pub fn new(frequency: f32, sample_rate: ModulationRate) -> Self {
         Self {
             frequency: Parameter::new(frequency, 0.0, 300.0),
+            level: Parameter::new(1.0, 0.0, 1.0),
             sample_rate,
             current_level: 0.0,
+            phase_offset: 0.0,
+            current_frequency: frequency,
+        }
+    }
+
+    fn time(&self, sample: ModulationSampleIndex) -> f32 {
+        (sample.0 % self.sample_rate.0 as u64) as f32 / self.sample_rate.0 as f32
+    }
+}
+
+const LOW_FREQUENCY_OSCILLATOR_PARAMETERS: &[ParameterDescriptor] = &[
+    ParameterDescriptor {
+        name: "frequency",
+        unit: ParameterUnit::Hertz,
+    },
+    ParameterDescriptor {
+        name: "level",
+        unit: ParameterUnit::None,
+    },
+];
+
+impl Parameterized for LowFrequencyOscillator {
+    fn type_name(&self) -> Option<&'static str> {
+        Some("lfo")
+    }
+
+    fn parameter_descriptors(&self) -> &'static [ParameterDescriptor] {
+        LOW_FREQUENCY_OSCILLATOR_PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<&Parameter> {
+        match index {
+            0 => Some(&self.frequency),
+            1 => Some(&self.level),
+            _ => None,
+        }
+    }
+
+    fn parameter_mut(&mut self, index: usize) -> Option<&mut Parameter> {
+        match index {
+            0 => Some(&mut self.frequency),
+            1 => Some(&mut self.level),
+            _ => None,
         }
     }
 }
 
 impl ModulationComponent for LowFrequencyOscillator {
     fn process_modulation(&mut self, sample: ModulationSampleIndex) {
-        let omega = 2.0 * std::f32::consts::PI * self.frequency.get_value();
-        let t = (sample.0 % self.sample_rate.0 as u64) as f32 / self.sample_rate.0 as f32;
-        self.current_level = (t * omega).sin();
+        let t = self.time(sample);
+        let new_frequency = self.frequency.final_value();
+
+        if new_frequency != self.current_frequency {
+            let old_domain =
+                2.0 * std::f32::consts::PI * self.current_frequency * t + self.phase_offset;
+            let new_domain = 2.0 * std::f32::consts::PI * new_frequency * t;
+            self.phase_offset = old_domain - new_domain;
+            self.current_frequency = new_frequency;
+        }
+
+        let omega = 2.0 * std::f32::consts::PI * self.current_frequency;
+        self.current_level = (t * omega + self.phase_offset).sin() * self.level.final_value();
     }
 
     fn get_current_level(&self) -> f32 {
         self.current_level
     }
+
+    fn apply_modulations(
+        &mut self,
+        modulators: &ModulationComponentsStore,
+        sample: AudioSampleIndex,
+    ) -> Result<()> {
+        let frequency = self.frequency.apply_modulations(modulators, sample);
+        let level = self.level.apply_modulations(modulators, sample);
+        frequency.and(level)
+    }
+
+    fn apply_automation(&mut self, sample: AudioSampleIndex) {
+        self.frequency.apply_automation(sample);
+        self.level.apply_automation(sample);
+    }
 }
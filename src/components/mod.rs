@@ -1,5 +1,9 @@
+mod envelope;
 mod low_frequency_oscillator;
 mod oscillator;
+mod voice_manager;
 
+pub use envelope::*;
 pub use low_frequency_oscillator::*;
 pub use oscillator::*;
+pub use voice_manager::*;
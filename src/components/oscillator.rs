@@ -1,7 +1,11 @@
+use crate::core::automation::earliest;
+use crate::core::buffers::MultiChannelSliceMut;
 use crate::core::concepts::{AudioSampleIndex, SamplingRate};
 use crate::core::parameter::Parameter;
-use crate::core::traits::AudioComponent;
+use crate::core::reflection::{ParameterDescriptor, ParameterUnit, Parameterized};
+use crate::core::traits::{AudioComponent, AudioInputs};
 use crate::core::ModulationComponentsStore;
+use crate::error::Result;
 use std::ops::Range;
 
 pub struct Oscillator {
@@ -9,6 +13,9 @@ pub struct Oscillator {
     pub phase_offset: f32,
     pub level: Parameter,
     sampling_rate: SamplingRate,
+    // The phase and frequency of the last sample while the frequency slides. The phase can't be
+    // derived from the sample index then, so it's accumulated instead.
+    sweep: Option<(f32, f32)>,
 }
 
 impl Oscillator {
@@ -18,21 +25,102 @@ impl Oscillator {
             phase_offset: 0.0,
             level: Parameter::new(1.0, 0.0, 1.0),
             sampling_rate,
+            sweep: None,
+        }
+    }
+
+    fn update_frequency<R, F: FnOnce(&mut Parameter) -> R>(
+        &mut self,
+        sample: AudioSampleIndex,
+        update: F,
+    ) -> R {
+        let old_cycle_length = self.sampling_rate.0 as f32 / self.frequency.final_value();
+        let old_t = (sample.0 as f32 % old_cycle_length) / self.sampling_rate.0 as f32;
+        let old_domain =
+            2.0 * std::f32::consts::PI * self.frequency.final_value() * old_t + self.phase_offset;
+
+        let result = update(&mut self.frequency);
+
+        let new_cycle_length = self.sampling_rate.0 as f32 / self.frequency.final_value();
+        let new_t = (sample.0 as f32 % new_cycle_length) / self.sampling_rate.0 as f32;
+        let new_domain = 2.0 * std::f32::consts::PI * self.frequency.final_value() * new_t;
+
+        self.phase_offset = old_domain - new_domain;
+        result
+    }
+}
+
+const OSCILLATOR_PARAMETERS: &[ParameterDescriptor] = &[
+    ParameterDescriptor {
+        name: "frequency",
+        unit: ParameterUnit::Hertz,
+    },
+    ParameterDescriptor {
+        name: "level",
+        unit: ParameterUnit::None,
+    },
+];
+
+impl Parameterized for Oscillator {
+    fn type_name(&self) -> Option<&'static str> {
+        Some("oscillator")
+    }
+
+    fn parameter_descriptors(&self) -> &'static [ParameterDescriptor] {
+        OSCILLATOR_PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<&Parameter> {
+        match index {
+            0 => Some(&self.frequency),
+            1 => Some(&self.level),
+            _ => None,
+        }
+    }
+
+    fn parameter_mut(&mut self, index: usize) -> Option<&mut Parameter> {
+        match index {
+            0 => Some(&mut self.frequency),
+            1 => Some(&mut self.level),
+            _ => None,
         }
     }
 }
 
 impl AudioComponent for Oscillator {
-    fn process_audio(&mut self, data: &mut [f32], sample_range: Range<AudioSampleIndex>) {
-        let omega = 2.0 * std::f32::consts::PI * self.frequency.final_value();
-        let cycle_length = self.sampling_rate.0 as f32 / self.frequency.final_value();
+    fn process_audio(
+        &mut self,
+        _: &AudioInputs,
+        output: &mut MultiChannelSliceMut,
+        sample_range: Range<AudioSampleIndex>,
+    ) {
+        let final_frequency = self.frequency.final_value();
+        let omega = 2.0 * std::f32::consts::PI * final_frequency;
+        let cycle_length = self.sampling_rate.0 as f32 / final_frequency;
+        let sampling_rate = self.sampling_rate.0 as f32;
 
         let range = sample_range.start.0..sample_range.end.0;
 
-        for (sample, sample_index) in data.iter_mut().zip(range) {
+        for (sample, sample_index) in output.channel_mut(0).iter_mut().zip(range) {
             let sample_index = AudioSampleIndex(sample_index);
-            let t = (sample_index.0 as f32 % cycle_length) / self.sampling_rate.0 as f32;
-            *sample = (t * omega + self.phase_offset).sin() * self.level.final_value();
+            let frequency = self.frequency.value_at(sample_index);
+            let t = (sample_index.0 as f32 % cycle_length) / sampling_rate;
+
+            // Sweeps advance by the average frequency of neighbouring samples, which follows
+            // linear ramps exactly.
+            let phase = match self.sweep {
+                Some((phase, previous)) => {
+                    phase + std::f32::consts::PI * (previous + frequency) / sampling_rate
+                }
+                None => t * omega + self.phase_offset,
+            };
+            if frequency != final_frequency {
+                self.sweep = Some((phase % std::f32::consts::TAU, frequency));
+            } else if self.sweep.take().is_some() {
+                self.phase_offset = (phase - t * omega) % std::f32::consts::TAU;
+            }
+
+            *sample = phase.sin() * self.level.value_at(sample_index);
         }
     }
 
@@ -40,20 +128,87 @@ impl AudioComponent for Oscillator {
         &mut self,
         modulators: &ModulationComponentsStore,
         sample: AudioSampleIndex,
-    ) {
-        let old_cycle_length = self.sampling_rate.0 as f32 / self.frequency.final_value();
-        let old_t = (sample.0 as f32 % old_cycle_length) / self.sampling_rate.0 as f32;
-        let old_domain =
-            2.0 * std::f32::consts::PI * self.frequency.final_value() * old_t + self.phase_offset;
+    ) -> Result<()> {
+        let frequency = self.update_frequency(sample, |frequency| {
+            frequency.apply_modulations(modulators, sample)
+        });
+        let level = self.level.apply_modulations(modulators, sample);
+        frequency.and(level)
+    }
+
+    fn apply_automation(&mut self, sample: AudioSampleIndex) {
+        // Retuning moves the phase offset, so only do it when there's automation to follow.
+        if self.frequency.automation().is_some() {
+            self.update_frequency(sample, |frequency| frequency.apply_automation(sample));
+        }
+        self.level.apply_automation(sample);
+    }
 
-        self.frequency.apply_modulations(modulators);
+    // Gating and velocity are left to an envelope further down the chain.
+    fn note_on(&mut self, note: u8, _velocity: u8, sample: AudioSampleIndex) {
+        let frequency = 440.0 * ((note as f32 - 69.0) / 12.0).exp2();
+        self.update_frequency(sample, |f| f.set_value(frequency));
+    }
 
-        let new_cycle_length = self.sampling_rate.0 as f32 / self.frequency.final_value();
-        let new_t = (sample.0 as f32 % new_cycle_length) / self.sampling_rate.0 as f32;
-        let new_domain = 2.0 * std::f32::consts::PI * self.frequency.final_value() * new_t;
+    fn next_automation_breakpoint(&self, sample: AudioSampleIndex) -> Option<AudioSampleIndex> {
+        earliest(
+            self.frequency.next_automation_breakpoint(sample),
+            self.level.next_automation_breakpoint(sample),
+        )
+    }
+}
 
-        self.phase_offset = old_domain - new_domain;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::automation::{AutomationLane, Segment};
+    use crate::core::buffers::MultiChannelBuffer;
+    use crate::core::concepts::Channels;
+
+    fn render(oscillator: &mut Oscillator, start: u64, samples: usize) -> Vec<f32> {
+        let mut buffer = MultiChannelBuffer::new(Channels(1), samples);
+        oscillator.apply_automation(AudioSampleIndex(start));
+        oscillator.process_audio(
+            &AudioInputs::empty(),
+            &mut buffer.slice_mut(0..samples),
+            AudioSampleIndex(start)..AudioSampleIndex(start + samples as u64),
+        );
+        buffer.slice(0..samples).channel(0).to_vec()
+    }
 
-        self.level.apply_modulations(modulators);
+    // A linear sweep from f0 to f1 taking T seconds has the phase 2π (f0 t + (f1 - f0) t² / 2T),
+    // and carries on from there at f1.
+    #[test]
+    fn follows_frequency_ramps_continuously() {
+        let sampling_rate = 48000.0;
+        let ramp = 4800;
+        let (f0, f1) = (440.0, 880.0);
+        let mut oscillator = Oscillator::new(f0, SamplingRate(48000));
+        oscillator.frequency.set_automation(
+            AutomationLane::default()
+                .with_breakpoint(AudioSampleIndex(0), f0, Segment::Step)
+                .with_breakpoint(AudioSampleIndex(ramp), f1, Segment::Linear),
+        );
+
+        let mut obtained = render(&mut oscillator, 0, ramp as usize);
+        obtained.extend(render(&mut oscillator, ramp, 4800));
+
+        let ramp_time = ramp as f64 / sampling_rate;
+        let phase = |t: f64| {
+            let t_ramp = t.min(ramp_time);
+            f0 as f64 * t_ramp
+                + (f1 - f0) as f64 * t_ramp * t_ramp / (2.0 * ramp_time)
+                + f1 as f64 * (t - t_ramp)
+        };
+        for (n, sample) in obtained.iter().enumerate() {
+            let expected = (2.0 * std::f64::consts::PI * phase(n as f64 / sampling_rate)).sin();
+            assert!(
+                (*sample as f64 - expected).abs() < 1e-3,
+                "sample {}: {} instead of {}",
+                n,
+                sample,
+                expected
+            );
+        }
     }
 }
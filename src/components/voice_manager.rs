@@ -0,0 +1,390 @@
+use crate::core::buffers::MultiChannelSliceMut;
+use crate::core::concepts::{AudioSampleIndex, Channels};
+use crate::core::engine::{Engine, EngineSpec};
+use crate::core::reflection::Parameterized;
+use crate::core::topology::AudioTopology;
+use crate::core::traits::{AudioComponent, AudioInputs};
+use crate::core::ModulationComponentsStore;
+use crate::error::Result;
+use std::ops::Range;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum VoiceStealing {
+    Oldest,
+    Quietest,
+    // Retriggers a voice already playing the note, otherwise steals the oldest.
+    SameNote,
+}
+
+struct Voice {
+    engine: Engine,
+    topology: AudioTopology,
+    note: Option<u8>,
+    released: bool,
+    started: u64,
+    peak: f32,
+}
+
+impl Voice {
+    fn is_idle(&self) -> bool {
+        self.note.is_none()
+    }
+
+    fn note_on(&mut self, note: u8, velocity: u8, started: u64) {
+        let sample = self.engine.current_sample();
+        for c in self.topology.audio_components.iter_components_mut() {
+            c.note_on(note, velocity, sample);
+        }
+        self.note = Some(note);
+        self.released = false;
+        self.started = started;
+    }
+
+    fn note_off(&mut self, note: u8) {
+        let sample = self.engine.current_sample();
+        for c in self.topology.audio_components.iter_components_mut() {
+            c.note_off(note, sample);
+        }
+        self.released = true;
+    }
+
+    fn is_active(&self) -> bool {
+        self.topology
+            .audio_components
+            .iter_components()
+            .any(|c| c.is_active())
+    }
+}
+
+// Plays every note on its own copy of a voice topology. Each voice runs its own engine, so it
+// keeps its own modulators and automation.
+pub struct VoiceManager {
+    voices: Vec<Voice>,
+    stealing: VoiceStealing,
+    channels: Channels,
+    notes_played: u64,
+    voice_buffer: Vec<f32>,
+}
+
+impl VoiceManager {
+    pub fn new<F>(
+        spec: EngineSpec,
+        number_of_voices: usize,
+        stealing: VoiceStealing,
+        mut build_voice: F,
+    ) -> Result<Self>
+    where
+        F: FnMut(&Engine) -> Result<AudioTopology>,
+    {
+        let mut voices = Vec::with_capacity(number_of_voices);
+        for _ in 0..number_of_voices {
+            let engine = Engine::new(spec);
+            let topology = build_voice(&engine)?;
+            voices.push(Voice {
+                engine,
+                topology,
+                note: None,
+                released: false,
+                started: 0,
+                peak: 0.0,
+            });
+        }
+
+        Ok(Self {
+            voices,
+            stealing,
+            channels: spec.channels,
+            notes_played: 0,
+            voice_buffer: vec![0.0; spec.max_samples_per_step * spec.channels.0 as usize],
+        })
+    }
+
+    pub fn number_of_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.iter().filter(|v| !v.is_idle()).count()
+    }
+
+    pub fn voice_topology(&self, voice: usize) -> Option<&AudioTopology> {
+        self.voices.get(voice).map(|v| &v.topology)
+    }
+
+    pub fn voice_topology_mut(&mut self, voice: usize) -> Option<&mut AudioTopology> {
+        self.voices.get_mut(voice).map(|v| &mut v.topology)
+    }
+
+    fn allocate_voice(&self, note: u8) -> Option<usize> {
+        if let Some(idle) = self.voices.iter().position(|v| v.is_idle()) {
+            return Some(idle);
+        }
+
+        // Voices already releasing go first, so held notes are only cut off when all of them are.
+        let any_released = self.voices.iter().any(|v| v.released);
+        let candidates =
+            || (0..self.voices.len()).filter(move |v| self.voices[*v].released || !any_released);
+        let oldest = || candidates().min_by_key(|v| self.voices[*v].started);
+        match self.stealing {
+            VoiceStealing::Oldest => oldest(),
+            VoiceStealing::Quietest => candidates().min_by(|a, b| {
+                self.voices[*a]
+                    .peak
+                    .partial_cmp(&self.voices[*b].peak)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            }),
+            VoiceStealing::SameNote => candidates()
+                .find(|v| self.voices[*v].note == Some(note))
+                .or_else(oldest),
+        }
+    }
+}
+
+impl Parameterized for VoiceManager {}
+
+impl AudioComponent for VoiceManager {
+    fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    fn process_audio(
+        &mut self,
+        _: &AudioInputs,
+        output: &mut MultiChannelSliceMut,
+        _: Range<AudioSampleIndex>,
+    ) {
+        let channels = self.channels.0 as usize;
+        let samples = output.samples();
+        output.fill(0.0);
+
+        for voice in self.voices.iter_mut() {
+            if voice.released && !voice.is_active() {
+                voice.note = None;
+            }
+            if voice.is_idle() {
+                continue;
+            }
+
+            let voice_output = &mut self.voice_buffer[..samples * channels];
+            voice.engine.advance(&mut voice.topology, voice_output);
+
+            voice.peak = 0.0;
+            for (index, frame) in voice_output.chunks(channels).enumerate() {
+                for (channel, sample) in frame.iter().enumerate() {
+                    output.channel_mut(channel)[index] += *sample;
+                    voice.peak = voice.peak.max(sample.abs());
+                }
+            }
+        }
+    }
+
+    fn apply_modulations(
+        &mut self,
+        _: &ModulationComponentsStore,
+        _: AudioSampleIndex,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn note_on(&mut self, note: u8, velocity: u8, _sample: AudioSampleIndex) {
+        if let Some(voice) = self.allocate_voice(note) {
+            self.notes_played += 1;
+            self.voices[voice].note_on(note, velocity, self.notes_played);
+        }
+    }
+
+    fn note_off(&mut self, note: u8, _sample: AudioSampleIndex) {
+        for voice in self
+            .voices
+            .iter_mut()
+            .filter(|v| v.note == Some(note) && !v.released)
+        {
+            voice.note_off(note);
+        }
+    }
+
+    fn control_change(&mut self, controller: u8, value: u8, _sample: AudioSampleIndex) {
+        for voice in self.voices.iter_mut() {
+            let sample = voice.engine.current_sample();
+            for c in voice.topology.audio_components.iter_components_mut() {
+                c.control_change(controller, value, sample);
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.voices.iter().any(|v| !v.is_idle())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Envelope, Oscillator};
+    use crate::core::buffers::MultiChannelBuffer;
+    use crate::core::concepts::{ModulationRate, SamplingRate};
+    use crate::core::routing::AudioInputIndex;
+    use crate::testing::{ConstantGenerator, NoteGenerator};
+
+    fn spec() -> EngineSpec {
+        EngineSpec::new(SamplingRate(48000), ModulationRate(100), Channels(1), 128)
+    }
+
+    fn note_voices(number_of_voices: usize, stealing: VoiceStealing) -> VoiceManager {
+        VoiceManager::new(spec(), number_of_voices, stealing, |engine| {
+            let mut topology = engine.create_empty_topology();
+            topology.add_component(NoteGenerator::default());
+            Ok(topology)
+        })
+        .unwrap()
+    }
+
+    fn render(manager: &mut VoiceManager, samples: usize) -> Vec<f32> {
+        let mut buffer = MultiChannelBuffer::new(Channels(1), samples);
+        let range = AudioSampleIndex(0)..AudioSampleIndex(samples as u64);
+        manager.process_audio(
+            &AudioInputs::empty(),
+            &mut buffer.slice_mut(0..samples),
+            range,
+        );
+        buffer.slice(0..samples).channel(0).to_vec()
+    }
+
+    fn level(note: u8) -> f32 {
+        note as f32 / 100.0 * 127.0 / 127.0
+    }
+
+    #[test]
+    fn plays_chords() {
+        let mut manager = note_voices(4, VoiceStealing::Oldest);
+
+        manager.note_on(10, 127, AudioSampleIndex(0));
+        manager.note_on(20, 127, AudioSampleIndex(0));
+
+        assert_eq!(manager.active_voices(), 2);
+        assert_eq!(render(&mut manager, 128), vec![level(10) + level(20); 128]);
+
+        manager.note_off(10, AudioSampleIndex(128));
+        assert_eq!(render(&mut manager, 128), vec![level(20); 128]);
+        assert_eq!(manager.active_voices(), 1);
+    }
+
+    fn steal_with(stealing: VoiceStealing, last_note: u8) -> (Vec<f32>, usize) {
+        let mut manager = note_voices(2, stealing);
+
+        manager.note_on(20, 127, AudioSampleIndex(0));
+        render(&mut manager, 128);
+        manager.note_on(10, 127, AudioSampleIndex(128));
+        render(&mut manager, 128);
+        manager.note_on(last_note, 127, AudioSampleIndex(256));
+
+        (render(&mut manager, 128), manager.active_voices())
+    }
+
+    #[test]
+    fn steals_voices_by_policy() {
+        assert_eq!(
+            steal_with(VoiceStealing::Oldest, 30),
+            (vec![level(30) + level(10); 128], 2)
+        );
+        assert_eq!(
+            steal_with(VoiceStealing::Quietest, 30),
+            (vec![level(20) + level(30); 128], 2)
+        );
+        assert_eq!(
+            steal_with(VoiceStealing::SameNote, 10),
+            (vec![level(20) + level(10); 128], 2)
+        );
+        assert_eq!(
+            steal_with(VoiceStealing::SameNote, 30),
+            (vec![level(30) + level(10); 128], 2)
+        );
+    }
+
+    fn enveloped_voices(number_of_voices: usize, stealing: VoiceStealing) -> VoiceManager {
+        VoiceManager::new(spec(), number_of_voices, stealing, |engine| {
+            let mut topology = engine.create_empty_topology();
+            let mut generator = ConstantGenerator::default();
+            generator.level.set_value(0.5);
+            let generator_id = topology.add_component(generator);
+            topology.disconnect_from_output(generator_id);
+
+            let mut envelope = Envelope::new(engine.spec.sampling_rate);
+            envelope.attack.set_value(0.0);
+            envelope.decay.set_value(0.0);
+            envelope.sustain.set_value(1.0);
+            envelope.release.set_value(0.01);
+            let envelope_id = topology.add_component(envelope);
+            topology.connect(generator_id, envelope_id, AudioInputIndex(0), 1.0)?;
+            Ok(topology)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn steals_releasing_voices_before_held_ones() {
+        for stealing in [
+            VoiceStealing::Oldest,
+            VoiceStealing::Quietest,
+            VoiceStealing::SameNote,
+        ] {
+            let mut manager = enveloped_voices(3, stealing);
+            for (index, note) in [10, 20, 30].iter().enumerate() {
+                manager.note_on(*note, 127, AudioSampleIndex(index as u64 * 128));
+                render(&mut manager, 128);
+            }
+            manager.note_off(20, AudioSampleIndex(384));
+            render(&mut manager, 128);
+
+            manager.note_on(40, 127, AudioSampleIndex(512));
+
+            let notes: Vec<_> = manager.voices.iter().map(|v| v.note).collect();
+            assert_eq!(notes, vec![Some(10), Some(40), Some(30)], "{:?}", stealing);
+        }
+    }
+
+    #[test]
+    fn releases_voices_after_envelope() {
+        let mut manager = enveloped_voices(2, VoiceStealing::Oldest);
+
+        manager.note_on(50, 127, AudioSampleIndex(0));
+        assert_eq!(render(&mut manager, 128), vec![0.5; 128]);
+
+        // A 10ms release takes 480 samples.
+        manager.note_off(50, AudioSampleIndex(128));
+        let released = render(&mut manager, 128);
+        assert!(released.windows(2).all(|w| w[1] < w[0]));
+        assert!(released[127] > 0.0);
+
+        for _ in 0..3 {
+            render(&mut manager, 128);
+        }
+        assert_eq!(manager.active_voices(), 1);
+        assert_eq!(render(&mut manager, 128), vec![0.0; 128]);
+        assert_eq!(manager.active_voices(), 0);
+    }
+
+    #[test]
+    fn follows_notes_with_oscillator_frequency() {
+        let mut manager = VoiceManager::new(spec(), 2, VoiceStealing::Oldest, |engine| {
+            let mut topology = engine.create_empty_topology();
+            let oscillator_id =
+                topology.add_component(Oscillator::new(100.0, engine.spec.sampling_rate));
+            topology.set_name(oscillator_id, "osc");
+            Ok(topology)
+        })
+        .unwrap();
+
+        manager.note_on(69, 127, AudioSampleIndex(0));
+        manager.note_on(81, 127, AudioSampleIndex(0));
+
+        let frequencies: Vec<f32> = (0..2)
+            .map(|voice| {
+                let topology = manager.voice_topology(voice).unwrap();
+                let frequency = topology.find_parameter("osc.frequency").unwrap();
+                topology.get_parameter(frequency).unwrap().get_value()
+            })
+            .collect();
+        assert_eq!(frequencies, vec![440.0, 880.0]);
+    }
+}
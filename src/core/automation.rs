@@ -0,0 +1,168 @@
+use crate::core::concepts::AudioSampleIndex;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Segment {
+    Step,
+    Linear,
+    Curved(f32),
+}
+
+impl Segment {
+    fn interpolate(&self, start: f32, end: f32, progress: f32) -> f32 {
+        match self {
+            Segment::Step => start,
+            Segment::Linear => start + (end - start) * progress,
+            Segment::Curved(curvature) if *curvature == 0.0 => start + (end - start) * progress,
+            Segment::Curved(curvature) => {
+                let shaped = (curvature * progress).exp_m1() / curvature.exp_m1();
+                start + (end - start) * shaped
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Breakpoint {
+    pub sample: AudioSampleIndex,
+    pub value: f32,
+    pub segment: Segment,
+}
+
+#[derive(Clone, Default)]
+pub struct AutomationLane {
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl AutomationLane {
+    // The segment describes how the lane reaches this breakpoint from the previous one.
+    pub fn add_breakpoint(&mut self, sample: AudioSampleIndex, value: f32, segment: Segment) {
+        let position = self.breakpoints.partition_point(|b| b.sample <= sample);
+        self.breakpoints.insert(
+            position,
+            Breakpoint {
+                sample,
+                value,
+                segment,
+            },
+        );
+    }
+
+    pub fn with_breakpoint(
+        mut self,
+        sample: AudioSampleIndex,
+        value: f32,
+        segment: Segment,
+    ) -> Self {
+        self.add_breakpoint(sample, value, segment);
+        self
+    }
+
+    pub fn iter_breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter()
+    }
+
+    pub fn value_at(&self, sample: AudioSampleIndex) -> Option<f32> {
+        let next = self.breakpoints.partition_point(|b| b.sample <= sample);
+
+        if next == 0 {
+            return self.breakpoints.first().map(|b| b.value);
+        }
+        let previous = &self.breakpoints[next - 1];
+        let next = match self.breakpoints.get(next) {
+            Some(next) => next,
+            None => return Some(previous.value),
+        };
+
+        let progress =
+            (sample - previous.sample).0 as f32 / (next.sample - previous.sample).0 as f32;
+        Some(
+            next.segment
+                .interpolate(previous.value, next.value, progress),
+        )
+    }
+
+    pub fn next_breakpoint_after(&self, sample: AudioSampleIndex) -> Option<AudioSampleIndex> {
+        let next = self.breakpoints.partition_point(|b| b.sample <= sample);
+        self.breakpoints.get(next).map(|b| b.sample)
+    }
+}
+
+pub fn earliest(
+    a: Option<AudioSampleIndex>,
+    b: Option<AudioSampleIndex>,
+) -> Option<AudioSampleIndex> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lane(segment: Segment) -> AutomationLane {
+        AutomationLane::default()
+            .with_breakpoint(AudioSampleIndex(100), 0.5, Segment::Linear)
+            .with_breakpoint(AudioSampleIndex(200), 1.0, segment)
+    }
+
+    #[test]
+    fn holds_values_outside_breakpoints() {
+        let lane = lane(Segment::Linear);
+
+        assert_eq!(
+            AutomationLane::default().value_at(AudioSampleIndex(0)),
+            None
+        );
+        assert_eq!(lane.value_at(AudioSampleIndex(0)), Some(0.5));
+        assert_eq!(lane.value_at(AudioSampleIndex(100)), Some(0.5));
+        assert_eq!(lane.value_at(AudioSampleIndex(200)), Some(1.0));
+        assert_eq!(lane.value_at(AudioSampleIndex(5000)), Some(1.0));
+    }
+
+    #[test]
+    fn interpolates_segments() {
+        assert_eq!(
+            lane(Segment::Linear).value_at(AudioSampleIndex(150)),
+            Some(0.75)
+        );
+        assert_eq!(
+            lane(Segment::Step).value_at(AudioSampleIndex(199)),
+            Some(0.5)
+        );
+        assert_eq!(
+            lane(Segment::Curved(0.0)).value_at(AudioSampleIndex(150)),
+            Some(0.75)
+        );
+
+        let slow_start = lane(Segment::Curved(3.0))
+            .value_at(AudioSampleIndex(150))
+            .unwrap();
+        let fast_start = lane(Segment::Curved(-3.0))
+            .value_at(AudioSampleIndex(150))
+            .unwrap();
+        assert!(0.5 < slow_start && slow_start < 0.75);
+        assert!(0.75 < fast_start && fast_start < 1.0);
+    }
+
+    #[test]
+    fn keeps_breakpoints_sorted() {
+        let lane = AutomationLane::default()
+            .with_breakpoint(AudioSampleIndex(300), 0.3, Segment::Linear)
+            .with_breakpoint(AudioSampleIndex(100), 0.1, Segment::Linear)
+            .with_breakpoint(AudioSampleIndex(200), 0.2, Segment::Linear);
+
+        assert_eq!(
+            lane.next_breakpoint_after(AudioSampleIndex(0)),
+            Some(AudioSampleIndex(100))
+        );
+        assert_eq!(
+            lane.next_breakpoint_after(AudioSampleIndex(100)),
+            Some(AudioSampleIndex(200))
+        );
+        assert_eq!(lane.next_breakpoint_after(AudioSampleIndex(300)), None);
+        assert_eq!(lane.value_at(AudioSampleIndex(250)), Some(0.25));
+    }
+}
@@ -1 +1,110 @@
-pub struct SingleChannelBuffer<'a>(pub &'a mut [f32]);
+use crate::core::concepts::Channels;
+use std::ops::Range;
+
+pub struct MultiChannelBuffer {
+    data: Vec<f32>,
+    channels: usize,
+    capacity: usize,
+}
+
+impl MultiChannelBuffer {
+    pub fn new(channels: Channels, capacity: usize) -> Self {
+        Self {
+            data: vec![0.0; channels.0 as usize * capacity],
+            channels: channels.0 as usize,
+            capacity,
+        }
+    }
+
+    pub fn channels(&self) -> Channels {
+        Channels(self.channels as u16)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> MultiChannelSlice<'_> {
+        assert!(range.end <= self.capacity);
+        MultiChannelSlice {
+            data: &self.data,
+            channels: self.channels,
+            stride: self.capacity,
+            range,
+        }
+    }
+
+    pub fn slice_mut(&mut self, range: Range<usize>) -> MultiChannelSliceMut<'_> {
+        assert!(range.end <= self.capacity);
+        MultiChannelSliceMut {
+            data: &mut self.data,
+            channels: self.channels,
+            stride: self.capacity,
+            range,
+        }
+    }
+}
+
+pub struct MultiChannelSlice<'a> {
+    data: &'a [f32],
+    channels: usize,
+    stride: usize,
+    range: Range<usize>,
+}
+
+impl<'a> MultiChannelSlice<'a> {
+    pub fn channels(&self) -> Channels {
+        Channels(self.channels as u16)
+    }
+
+    pub fn samples(&self) -> usize {
+        self.range.len()
+    }
+
+    pub fn channel(&self, channel: usize) -> &'a [f32] {
+        let start = channel * self.stride;
+        &self.data[start + self.range.start..start + self.range.end]
+    }
+
+    pub fn interleave_into(&self, output: &mut [f32]) {
+        assert_eq!(output.len(), self.samples() * self.channels);
+        for channel in 0..self.channels {
+            for (sample, value) in self.channel(channel).iter().enumerate() {
+                output[sample * self.channels + channel] = *value;
+            }
+        }
+    }
+}
+
+pub struct MultiChannelSliceMut<'a> {
+    data: &'a mut [f32],
+    channels: usize,
+    stride: usize,
+    range: Range<usize>,
+}
+
+impl<'a> MultiChannelSliceMut<'a> {
+    pub fn channels(&self) -> Channels {
+        Channels(self.channels as u16)
+    }
+
+    pub fn samples(&self) -> usize {
+        self.range.len()
+    }
+
+    pub fn channel(&self, channel: usize) -> &[f32] {
+        let start = channel * self.stride;
+        &self.data[start + self.range.start..start + self.range.end]
+    }
+
+    pub fn channel_mut(&mut self, channel: usize) -> &mut [f32] {
+        let start = channel * self.stride;
+        &mut self.data[start + self.range.start..start + self.range.end]
+    }
+
+    pub fn fill(&mut self, value: f32) {
+        for channel in 0..self.channels {
+            self.channel_mut(channel).fill(value);
+        }
+    }
+}
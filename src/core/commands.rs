@@ -0,0 +1,140 @@
+use crate::core::concepts::AudioSampleDifference;
+use crate::core::parameter::ModulationMapping;
+use crate::core::reflection::ParameterId;
+use crate::core::topology::{AudioComponentId, ModulationComponentId};
+use crate::error::{Error, Result};
+use rtrb::{Consumer, Producer, RingBuffer};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Command {
+    SetParameter {
+        parameter: ParameterId,
+        value: f32,
+    },
+    AddModulation {
+        parameter: ParameterId,
+        modulator: ModulationComponentId,
+        level: f32,
+        mapping: ModulationMapping,
+    },
+    RemoveModulation {
+        parameter: ParameterId,
+        modulator: ModulationComponentId,
+    },
+    NoteOn {
+        component: AudioComponentId,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        component: AudioComponentId,
+        note: u8,
+    },
+    ControlChange {
+        component: AudioComponentId,
+        controller: u8,
+        value: u8,
+    },
+    SetBypass {
+        component: AudioComponentId,
+        bypassed: bool,
+    },
+}
+
+// A command that takes effect a number of samples into the block being rendered.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TimedCommand {
+    pub offset: AudioSampleDifference,
+    pub command: Command,
+}
+
+// Commands that fail on the audio thread are reported back through a second queue, so neither
+// direction allocates or blocks.
+pub fn command_queue(capacity: usize) -> (CommandSender, CommandReceiver) {
+    let (commands, pending_commands) = RingBuffer::new(capacity);
+    let (errors, pending_errors) = RingBuffer::new(capacity);
+
+    (
+        CommandSender {
+            commands,
+            errors: pending_errors,
+        },
+        CommandReceiver {
+            commands: pending_commands,
+            errors,
+        },
+    )
+}
+
+pub struct CommandSender {
+    commands: Producer<Command>,
+    errors: Consumer<Error>,
+}
+
+impl CommandSender {
+    pub fn send(&mut self, command: Command) -> Result<()> {
+        self.commands
+            .push(command)
+            .map_err(|_| Error::CommandQueueFull)
+    }
+
+    pub fn pop_error(&mut self) -> Option<Error> {
+        self.errors.pop().ok()
+    }
+}
+
+pub struct CommandReceiver {
+    commands: Consumer<Command>,
+    errors: Producer<Error>,
+}
+
+impl CommandReceiver {
+    pub fn pop(&mut self) -> Option<Command> {
+        self.commands.pop().ok()
+    }
+
+    // Errors are dropped when nobody collects them and the queue fills up.
+    pub fn report(&mut self, error: Error) {
+        let _ = self.errors.push(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::reflection::ParameterOwner;
+    use crate::core::ModulationComponentsStore;
+    use crate::testing::AlternatingModulator;
+
+    fn set_parameter(value: f32) -> Command {
+        let mut store = ModulationComponentsStore::default();
+        let id = store.add_component(Box::new(AlternatingModulator::new(0.0)));
+        Command::SetParameter {
+            parameter: ParameterId {
+                owner: ParameterOwner::Modulation(id),
+                index: 0,
+            },
+            value,
+        }
+    }
+
+    #[test]
+    fn delivers_commands_in_order() {
+        let (mut sender, mut receiver) = command_queue(2);
+
+        sender.send(set_parameter(0.25)).unwrap();
+        sender.send(set_parameter(0.5)).unwrap();
+        assert_eq!(
+            sender.send(set_parameter(0.75)),
+            Err(Error::CommandQueueFull)
+        );
+
+        assert_eq!(receiver.pop(), Some(set_parameter(0.25)));
+        assert_eq!(receiver.pop(), Some(set_parameter(0.5)));
+        assert_eq!(receiver.pop(), None);
+
+        receiver.report(Error::UnknownParameter);
+        assert_eq!(sender.pop_error(), Some(Error::UnknownParameter));
+        assert_eq!(sender.pop_error(), None);
+    }
+}
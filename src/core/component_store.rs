@@ -1,63 +1,233 @@
 use std::marker::PhantomData;
 
-#[derive(Copy, Clone, PartialEq)]
-pub struct ComponentId<T>(pub usize, PhantomData<T>);
+// Counts the slots lookups inspect, so tests can check lookup cost without timing it.
+#[cfg(test)]
+thread_local! {
+    static PROBES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+#[cfg(test)]
+pub fn take_probes() -> usize {
+    PROBES.with(|probes| probes.replace(0))
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ComponentId<T> {
+    index: usize,
+    generation: u32,
+    tag: PhantomData<T>,
+}
 
 impl<T> ComponentId<T> {
-    fn new(id: usize) -> Self {
-        ComponentId(id, PhantomData)
+    fn new(index: usize, generation: u32) -> Self {
+        ComponentId {
+            index,
+            generation,
+            tag: PhantomData,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
     }
 }
 
-struct StoredComponent<T: ?Sized, Id> {
-    id: ComponentId<Id>,
-    data: Box<T>,
+struct Slot<T: ?Sized> {
+    generation: u32,
+    data: Option<Box<T>>,
 }
 
 pub struct ComponentsStore<T: ?Sized, Id> {
-    components: Vec<StoredComponent<T, Id>>,
-    created_elements: usize,
+    slots: Vec<Slot<T>>,
+    free_indices: Vec<usize>,
+    len: usize,
+    tag: PhantomData<Id>,
 }
 
 impl<T: ?Sized, Id> Default for ComponentsStore<T, Id> {
     fn default() -> Self {
         Self {
-            components: vec![],
-            created_elements: 0,
+            slots: vec![],
+            free_indices: vec![],
+            len: 0,
+            tag: PhantomData,
         }
     }
 }
 
 impl<T: ?Sized, Id: Copy + Clone + PartialEq> ComponentsStore<T, Id> {
     pub fn add_component(&mut self, component: Box<T>) -> ComponentId<Id> {
-        let id = ComponentId::new(self.created_elements);
-        self.components.push(StoredComponent {
-            id,
-            data: component,
-        });
-        self.created_elements += 1;
-        id
+        let index = match self.free_indices.pop() {
+            Some(index) => {
+                self.slots[index].data = Some(component);
+                index
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    data: Some(component),
+                });
+                self.slots.len() - 1
+            }
+        };
+
+        self.len += 1;
+        ComponentId::new(index, self.slots[index].generation)
+    }
+
+    pub fn remove_component(&mut self, id: ComponentId<Id>) -> Option<Box<T>> {
+        let slot = self.slots.get_mut(id.index)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+
+        let removed = slot.data.take()?;
+        slot.generation += 1;
+        self.free_indices.push(id.index);
+        self.len -= 1;
+
+        Some(removed)
+    }
+
+    pub fn take_component(&mut self, id: ComponentId<Id>) -> Option<Box<T>> {
+        self.slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.data.take())
+    }
+
+    pub fn restore_component(&mut self, id: ComponentId<Id>, component: Box<T>) {
+        let slot = &mut self.slots[id.index];
+        assert!(slot.generation == id.generation && slot.data.is_none());
+        slot.data = Some(component);
+    }
+
+    pub fn contains(&self, id: ComponentId<Id>) -> bool {
+        self.get_component(id).is_some()
     }
 
     pub fn get_component(&self, id: ComponentId<Id>) -> Option<&T> {
-        self.components
-            .iter()
-            .find(|node| node.id == id)
-            .map(|m| m.data.as_ref())
+        #[cfg(test)]
+        PROBES.with(|probes| probes.set(probes.get() + 1));
+
+        self.slots
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.data.as_deref())
     }
 
     pub fn get_component_mut(&mut self, id: ComponentId<Id>) -> Option<&mut T> {
-        self.components
-            .iter_mut()
-            .find(|node| node.id == id)
-            .map(|m| m.data.as_mut())
+        self.slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.data.as_deref_mut())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Ids index below this, whether their slot is taken or not.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn id_at(&self, index: usize) -> Option<ComponentId<Id>> {
+        self.slots
+            .get(index)
+            .filter(|slot| slot.data.is_some())
+            .map(|slot| ComponentId::new(index, slot.generation))
+    }
+
+    pub fn iter_ids(&self) -> impl Iterator<Item = ComponentId<Id>> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.data.is_some())
+            .map(|(index, slot)| ComponentId::new(index, slot.generation))
     }
 
     pub fn iter_components(&self) -> impl Iterator<Item = &T> {
-        self.components.iter().map(|n| n.data.as_ref())
+        self.slots.iter().filter_map(|slot| slot.data.as_deref())
     }
 
     pub fn iter_components_mut(&mut self) -> impl Iterator<Item = &mut T> {
-        self.components.iter_mut().map(|n| n.data.as_mut())
+        self.slots
+            .iter_mut()
+            .filter_map(|slot| slot.data.as_deref_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    struct TestTag;
+
+    #[test]
+    fn removes_components() {
+        let mut store = ComponentsStore::<u32, TestTag>::default();
+        let id1 = store.add_component(Box::new(1));
+        let id2 = store.add_component(Box::new(2));
+
+        assert_eq!(store.remove_component(id1), Some(Box::new(1)));
+        assert_eq!(store.remove_component(id1), None);
+        assert_eq!(store.get_component(id1), None);
+        assert_eq!(store.get_component(id2), Some(&2));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn detects_stale_ids_after_index_reuse() {
+        let mut store = ComponentsStore::<u32, TestTag>::default();
+        let stale_id = store.add_component(Box::new(1));
+        store.remove_component(stale_id);
+
+        let new_id = store.add_component(Box::new(2));
+
+        assert_eq!(new_id.index(), stale_id.index());
+        assert_ne!(new_id, stale_id);
+        assert_eq!(store.get_component(stale_id), None);
+        assert_eq!(store.get_component(new_id), Some(&2));
+    }
+
+    #[test]
+    fn keeps_ids_stable_across_removals() {
+        let mut store = ComponentsStore::<u32, TestTag>::default();
+        let ids: Vec<_> = (0..10).map(|i| store.add_component(Box::new(i))).collect();
+
+        for id in ids.iter().step_by(2) {
+            store.remove_component(*id);
+        }
+
+        for (i, id) in ids.iter().enumerate().skip(1).step_by(2) {
+            assert_eq!(store.get_component(*id), Some(&(i as u32)));
+        }
+        assert_eq!(store.iter_ids().count(), 5);
+    }
+
+    // Lookups go straight to the slot an id names, so their cost doesn't depend on how many
+    // components the store holds.
+    #[test]
+    fn ids_address_their_slot_directly() {
+        let mut store = ComponentsStore::<u32, TestTag>::default();
+        let ids: Vec<_> = (0..2000)
+            .map(|i| store.add_component(Box::new(i)))
+            .collect();
+
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(id.index(), i);
+        }
+        assert_eq!(store.get_component(ComponentId::new(1999, 0)), Some(&1999));
+        assert_eq!(store.get_component(ComponentId::new(2000, 0)), None);
     }
 }
@@ -1,9 +1,9 @@
 use derive_more::{Add, AddAssign, Sub, SubAssign};
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
 pub struct AudioSampleIndex(pub u64);
 
-#[derive(Copy, Clone, Add, AddAssign, Sub, SubAssign, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Copy, Clone, Add, AddAssign, Sub, SubAssign, Ord, PartialOrd, Eq, PartialEq, Debug)]
 pub struct AudioSampleDifference(pub u64);
 
 impl std::ops::Add<AudioSampleDifference> for AudioSampleIndex {
@@ -1,14 +1,30 @@
+use crate::core::automation::earliest;
+use crate::core::buffers::{MultiChannelSlice, MultiChannelSliceMut};
+use crate::core::commands::{Command, CommandReceiver, TimedCommand};
 use crate::core::concepts::{
     AudioSampleDifference, AudioSampleIndex, Channels, ModulationRate, ModulationSampleIndex,
     SamplingRate,
 };
+use crate::core::mixer::{mix_channels, Mixer};
+use crate::core::routing::AudioRouting;
 use crate::core::topology::AudioTopology;
-use crate::core::{AudioComponentsStore, ModulationComponentsStore};
+use crate::core::topology_swap::TopologyReceiver;
+use crate::core::{AudioComponentsStore, ModulationComponentId, ModulationComponentsStore};
+use crate::error::{Error, Result};
+use std::convert::TryInto;
+use std::ops::RangeInclusive;
+
+// The settings an engine can run with. Modulation rates go from 1 up to the sampling rate, since
+// every modulation tick needs an audio sample of its own.
+pub const SAMPLING_RATES: RangeInclusive<u32> = 1..=768_000;
+pub const CHANNELS: RangeInclusive<u16> = 1..=32;
+pub const STEP_SIZES: RangeInclusive<usize> = 1..=65536;
 
 #[derive(Copy, Clone)]
 pub struct EngineSpec {
     pub sampling_rate: SamplingRate,
     pub modulation_rate: ModulationRate,
+    // Rounded down. Ticks are scheduled with `modulation_sample`, which doesn't drift.
     pub modulation_period: AudioSampleDifference,
     pub channels: Channels,
     pub max_samples_per_step: usize,
@@ -21,21 +37,80 @@ impl EngineSpec {
         channels: Channels,
         max_samples_per_step: usize,
     ) -> Self {
-        Self {
+        match Self::try_new(
             sampling_rate,
             modulation_rate,
-            modulation_period: AudioSampleDifference((sampling_rate.0 / modulation_rate.0) as u64),
             channels,
             max_samples_per_step,
+        ) {
+            Ok(spec) => spec,
+            Err(e) => panic!("{}", e),
         }
     }
+
+    pub fn try_new(
+        sampling_rate: SamplingRate,
+        modulation_rate: ModulationRate,
+        channels: Channels,
+        max_samples_per_step: usize,
+    ) -> Result<Self> {
+        check_setting("sampling_rate", sampling_rate.0, SAMPLING_RATES)?;
+        check_setting("modulation_rate", modulation_rate.0, 1..=sampling_rate.0)?;
+        check_setting("channels", channels.0, CHANNELS)?;
+        check_setting("max_samples_per_step", max_samples_per_step, STEP_SIZES)?;
+
+        Ok(Self {
+            sampling_rate,
+            modulation_rate,
+            modulation_period: AudioSampleDifference((sampling_rate.0 / modulation_rate.0) as u64),
+            channels,
+            max_samples_per_step,
+        })
+    }
+
+    // Every tick is placed relative to the start instead of the previous tick, so rounding to
+    // whole samples never accumulates and the long-run modulation rate stays exact.
+    pub fn modulation_sample(&self, modulation_sample: ModulationSampleIndex) -> AudioSampleIndex {
+        AudioSampleIndex(
+            modulation_sample.0 * self.sampling_rate.0 as u64 / self.modulation_rate.0 as u64,
+        )
+    }
+
+    pub fn is_compatible(&self, other: &EngineSpec) -> bool {
+        self.sampling_rate.0 == other.sampling_rate.0
+            && self.modulation_rate.0 == other.modulation_rate.0
+            && self.channels.0 == other.channels.0
+            && self.max_samples_per_step <= other.max_samples_per_step
+    }
+}
+
+fn check_setting<T>(field: &'static str, value: T, range: RangeInclusive<T>) -> Result<()>
+where
+    T: PartialOrd + TryInto<u64>,
+{
+    if range.contains(&value) {
+        return Ok(());
+    }
+    Err(Error::InvalidEngine {
+        field,
+        value: value.try_into().unwrap_or(u64::MAX),
+    })
+}
+
+struct Crossfade {
+    topology: Box<AudioTopology>,
+    length: AudioSampleDifference,
+    elapsed: AudioSampleDifference,
 }
 
 pub struct Engine {
     pub spec: EngineSpec,
     current_audio_sample: AudioSampleIndex,
     current_modulation_sample: ModulationSampleIndex,
-    last_audio_sample_with_modulation: AudioSampleIndex,
+    commands: Option<CommandReceiver>,
+    topologies: Option<TopologyReceiver>,
+    crossfade: Option<Crossfade>,
+    crossfade_buffer: Vec<f32>,
 }
 
 impl Engine {
@@ -44,100 +119,303 @@ impl Engine {
             spec,
             current_audio_sample: AudioSampleIndex(0),
             current_modulation_sample: ModulationSampleIndex(0),
-            last_audio_sample_with_modulation: AudioSampleIndex(0),
+            commands: None,
+            topologies: None,
+            crossfade: None,
+            crossfade_buffer: vec![0.0; spec.max_samples_per_step * spec.channels.0 as usize],
         }
     }
 
+    pub fn current_sample(&self) -> AudioSampleIndex {
+        self.current_audio_sample
+    }
+
+    pub fn set_command_receiver(&mut self, receiver: CommandReceiver) {
+        self.commands = Some(receiver);
+    }
+
+    pub fn set_topology_receiver(&mut self, receiver: TopologyReceiver) {
+        self.topologies = Some(receiver);
+    }
+
     pub fn create_empty_topology(&self) -> AudioTopology {
         AudioTopology::new(self.spec)
     }
 
     pub fn advance(&mut self, topology: &mut AudioTopology, audio: &mut [f32]) {
+        self.advance_with_events(topology, audio, &[]);
+    }
+
+    // Events have to be sorted by offset and fall within the block, later ones belong to the
+    // call for the block they fall in.
+    pub fn advance_with_events(
+        &mut self,
+        topology: &mut AudioTopology,
+        audio: &mut [f32],
+        events: &[TimedCommand],
+    ) {
+        let channels = self.spec.channels.0 as usize;
+        let frames = AudioSampleDifference((audio.len() / channels) as u64);
+        debug_assert!(events.windows(2).all(|w| w[0].offset <= w[1].offset));
+        debug_assert!(
+            events.iter().all(|e| e.offset < frames),
+            "events have to fall within the block"
+        );
+
+        // Devices may ask for more than max_samples_per_step, so larger blocks are split into
+        // sub-blocks that fit the processing buffers. Event offsets stay relative to the start of
+        // the whole block.
+        let step = self.spec.max_samples_per_step * channels;
+        let events_start = self.current_audio_sample;
+        let mut events = events;
+        let mut start = 0;
+        loop {
+            let end = (start + step).min(audio.len());
+            let sub_block_events = if end == audio.len() {
+                events.len()
+            } else {
+                let sub_block_end = AudioSampleDifference((end / channels) as u64);
+                events.partition_point(|e| e.offset < sub_block_end)
+            };
+            let (current, later) = events.split_at(sub_block_events);
+
+            self.advance_block(topology, &mut audio[start..end], current, events_start);
+
+            if end == audio.len() {
+                break;
+            }
+            events = later;
+            start = end;
+        }
+    }
+
+    fn advance_block(
+        &mut self,
+        topology: &mut AudioTopology,
+        audio: &mut [f32],
+        events: &[TimedCommand],
+        events_start: AudioSampleIndex,
+    ) {
+        self.receive_topology(topology);
+        self.apply_commands(topology);
+
+        let mut crossfade = match self.crossfade.take() {
+            Some(crossfade) => crossfade,
+            None => return self.render(topology, audio, events, events_start),
+        };
+
+        // Both topologies render the same stretch of time, only the new one receives events.
+        let clock = self.clock();
+        let mut faded_out = std::mem::take(&mut self.crossfade_buffer);
+        self.render(
+            &mut crossfade.topology,
+            &mut faded_out[..audio.len()],
+            &[],
+            events_start,
+        );
+        self.set_clock(clock);
+        self.render(topology, audio, events, events_start);
+
+        let channels = self.spec.channels.0 as usize;
+        for (frame, faded_out_frame) in audio.chunks_mut(channels).zip(faded_out.chunks(channels)) {
+            let fade_in = (crossfade.elapsed.0 as f32 / crossfade.length.0 as f32).min(1.0);
+            for (sample, faded_out_sample) in frame.iter_mut().zip(faded_out_frame) {
+                *sample = *sample * fade_in + *faded_out_sample * (1.0 - fade_in);
+            }
+            crossfade.elapsed += AudioSampleDifference(1);
+        }
+        self.crossfade_buffer = faded_out;
+
+        if crossfade.elapsed >= crossfade.length {
+            self.retire(crossfade.topology);
+        } else {
+            self.crossfade = Some(crossfade);
+        }
+    }
+
+    fn render(
+        &mut self,
+        topology: &mut AudioTopology,
+        audio: &mut [f32],
+        events: &[TimedCommand],
+        events_start: AudioSampleIndex,
+    ) {
         let total_samples =
             AudioSampleDifference((audio.len() / self.spec.channels.0 as usize) as u64);
         let start_sample = self.current_audio_sample;
+        let end_sample = start_sample + total_samples;
         assert_eq!(total_samples * self.spec.channels, audio.len());
+        let mut pending_events = events.iter().peekable();
 
-        let buffer = &mut topology.processing_buffer.as_mut_slice()[0..total_samples.0 as usize];
+        while self.current_audio_sample < end_sample {
+            while let Some(event) =
+                pending_events.next_if(|e| events_start + e.offset <= self.current_audio_sample)
+            {
+                self.apply_command(topology, event.command);
+            }
 
-        if self.current_audio_sample == AudioSampleIndex(0) {
-            self.process_modulation(
-                &mut topology.modulation_components,
+            let next_modulation = self.spec.modulation_sample(self.current_modulation_sample);
+            if next_modulation == self.current_audio_sample {
+                self.process_modulation(
+                    &mut topology.modulation_components,
+                    &topology.modulation_order,
+                    &mut topology.audio_components,
+                );
+                continue;
+            }
+
+            let next_automation = self.apply_automation(&mut topology.audio_components);
+            let next_event = pending_events.peek().map(|e| events_start + e.offset);
+            let split_sample =
+                earliest(earliest(Some(next_modulation), next_automation), next_event)
+                    .unwrap()
+                    .min(end_sample);
+
+            let start_offset = (self.current_audio_sample - start_sample).0 as usize;
+            let end_offset = (split_sample - start_sample).0 as usize;
+            self.process_audio(
                 &mut topology.audio_components,
+                &mut topology.routing,
+                &topology.mixer,
+                &mut topology
+                    .processing_buffer
+                    .slice_mut(start_offset..end_offset),
+                split_sample - self.current_audio_sample,
             );
         }
 
-        let samples_before_next_modulation =
-            (self.last_audio_sample_with_modulation + self.spec.modulation_period) - start_sample;
-
-        if samples_before_next_modulation > total_samples {
-            self.process_audio(&mut topology.audio_components, buffer, total_samples);
-            self.mix_output(buffer, audio);
-            return;
+        // Only reached by events outside of the block, which release builds apply late instead
+        // of dropping.
+        for event in pending_events {
+            self.apply_command(topology, event.command);
         }
 
-        self.process_audio(
-            &mut topology.audio_components,
-            &mut buffer[0..samples_before_next_modulation.0 as usize],
-            samples_before_next_modulation,
+        self.mix_output(
+            &topology
+                .processing_buffer
+                .slice(0..total_samples.0 as usize),
+            audio,
         );
-        let mut samples_remaining = total_samples - samples_before_next_modulation;
+    }
+
+    // A new topology replaces the current one right away, and the replaced one keeps playing
+    // until it has faded out. Swapping again mid-fade cuts the older fade short.
+    fn receive_topology(&mut self, topology: &mut AudioTopology) {
+        let swap = match self.topologies.as_mut().and_then(|r| r.pop()) {
+            Some(swap) => swap,
+            None => return,
+        };
 
-        let mut current_sample_start_offset = samples_before_next_modulation;
-        let audio_samples = self.spec.modulation_period;
+        let mut replaced = swap.topology;
+        std::mem::swap(topology, &mut *replaced);
 
-        while samples_remaining > AudioSampleDifference(0) {
+        // At sample 0 the first render takes care of the initial modulation pass.
+        if self.current_modulation_sample != ModulationSampleIndex(0) {
+            let clock = self.clock();
             self.process_modulation(
                 &mut topology.modulation_components,
+                &topology.modulation_order,
                 &mut topology.audio_components,
             );
-            let samples_to_process = samples_remaining.min(audio_samples);
-            let current_sample_end_offset = current_sample_start_offset + samples_to_process;
-            self.process_audio(
-                &mut topology.audio_components,
-                &mut buffer
-                    [current_sample_start_offset.0 as usize..current_sample_end_offset.0 as usize],
-                samples_to_process,
-            );
-            current_sample_start_offset = current_sample_end_offset;
+            self.set_clock(clock);
+        }
 
-            samples_remaining -= samples_to_process;
+        if let Some(previous) = self.crossfade.take() {
+            self.retire(previous.topology);
         }
+        if swap.crossfade.0 == 0 {
+            self.retire(replaced);
+        } else {
+            self.crossfade = Some(Crossfade {
+                topology: replaced,
+                length: swap.crossfade,
+                elapsed: AudioSampleDifference(0),
+            });
+        }
+    }
 
-        self.mix_output(buffer, audio);
+    fn retire(&mut self, topology: Box<AudioTopology>) {
+        if let Some(receiver) = self.topologies.as_mut() {
+            receiver.retire(topology);
+        }
     }
 
-    fn mix_output(&self, mono_output: &[f32], stereo_output: &mut [f32]) {
-        let mut i = 0;
-        for s in mono_output {
-            for _ in 0..self.spec.channels.0 {
-                stereo_output[i] = *s;
-                i += 1;
-            }
+    fn clock(&self) -> (AudioSampleIndex, ModulationSampleIndex) {
+        (self.current_audio_sample, self.current_modulation_sample)
+    }
+
+    fn set_clock(&mut self, clock: (AudioSampleIndex, ModulationSampleIndex)) {
+        self.current_audio_sample = clock.0;
+        self.current_modulation_sample = clock.1;
+    }
+
+    fn apply_commands(&mut self, topology: &mut AudioTopology) {
+        while let Some(command) = self.commands.as_mut().and_then(|r| r.pop()) {
+            self.apply_command(topology, command);
+        }
+    }
+
+    fn apply_command(&mut self, topology: &mut AudioTopology, command: Command) {
+        let result = topology.apply_command(command, self.current_audio_sample);
+        self.report(result);
+    }
+
+    // Failures are reported through the command queue, if there is one.
+    fn report(&mut self, result: Result<()>) {
+        if let (Err(e), Some(receiver)) = (result, self.commands.as_mut()) {
+            receiver.report(e);
+        }
+    }
+
+    fn apply_automation(
+        &mut self,
+        components: &mut AudioComponentsStore,
+    ) -> Option<AudioSampleIndex> {
+        let mut next_breakpoint = None;
+        for c in components.iter_components_mut() {
+            c.apply_automation(self.current_audio_sample);
+            next_breakpoint = earliest(
+                next_breakpoint,
+                c.next_automation_breakpoint(self.current_audio_sample),
+            );
         }
+        next_breakpoint
+    }
+
+    fn mix_output(&self, output: &MultiChannelSlice, interleaved_output: &mut [f32]) {
+        output.interleave_into(interleaved_output);
     }
 
     fn process_modulation(
         &mut self,
         modulators: &mut ModulationComponentsStore,
+        modulation_order: &[ModulationComponentId],
         components: &mut AudioComponentsStore,
     ) {
-        for m in modulators.iter_components_mut() {
-            m.process_modulation(self.current_modulation_sample);
+        for id in modulation_order {
+            if let Some(mut m) = modulators.take_component(*id) {
+                m.apply_automation(self.current_audio_sample);
+                let result = m.apply_modulations(modulators, self.current_audio_sample);
+                m.process_modulation(self.current_modulation_sample);
+                modulators.restore_component(*id, m);
+                self.report(result);
+            }
         }
 
         for c in components.iter_components_mut() {
-            c.apply_modulations(modulators, self.current_audio_sample);
+            let result = c.apply_modulations(modulators, self.current_audio_sample);
+            self.report(result);
         }
 
-        self.last_audio_sample_with_modulation = self.current_audio_sample;
         self.current_modulation_sample += ModulationSampleIndex(1);
     }
 
     fn process_audio(
         &mut self,
         components: &mut AudioComponentsStore,
-        audio: &mut [f32],
+        routing: &mut AudioRouting,
+        mixer: &Mixer,
+        output: &mut MultiChannelSliceMut,
         total_samples: AudioSampleDifference,
     ) {
         if total_samples.0 == 0 {
@@ -147,8 +425,13 @@ impl Engine {
         let start_sample = self.current_audio_sample;
         let end_sample = start_sample + total_samples;
 
-        for c in components.iter_components_mut() {
-            c.process_audio(audio, start_sample..end_sample);
+        routing.process(components, start_sample..end_sample);
+
+        output.fill(0.0);
+        for input in mixer.iter_inputs() {
+            if let Some(component_output) = routing.get_output(input.component, output.samples()) {
+                mix_channels(output, &component_output, input.gain, input.pan);
+            }
         }
 
         self.current_audio_sample = end_sample;
@@ -172,8 +455,20 @@ pub fn empty_engine(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::testing::{AlternatingModulator, ConstantGenerator};
+    use crate::core::automation::{AutomationLane, Segment};
+    use crate::core::commands::{command_queue, Command, TimedCommand};
+    use crate::core::topology_swap::topology_swap_queue;
+    use crate::error::Error;
+    use crate::core::parameter::{ModulationCurve, ModulationMapping, Polarity, Smoothing};
+    use crate::core::routing::{AudioInputIndex, RoutingError};
+    use crate::core::modulation::ModulationError;
+    use crate::testing::{
+        AlternatingModulator, ConstantGenerator, ConstantStereoGenerator, FollowingModulator,
+        NoteGenerator, ScalingEffect,
+    };
     use crate::core::topology::AudioTopology;
+    use crate::core::component_store::take_probes;
+    use std::time::Instant;
 
     fn run_engine(engine: &mut Engine, topology: &mut AudioTopology, test_steps: usize) -> Vec<f32> {
         let channels = engine.spec.channels.0 as usize;
@@ -187,7 +482,7 @@ mod tests {
             let mut buffer = vec![f32::NAN; samples];
 
             engine.advance(topology, buffer.as_mut_slice());
-            obtained.extend(buffer.into_iter());
+            obtained.extend(buffer);
 
             steps_so_far += steps;
         }
@@ -214,6 +509,134 @@ mod tests {
         assert_eq!(obtained, vec![0.1; test_samples]);
     }
 
+    #[test]
+    fn sums_components() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let mut generator1 = ConstantGenerator::default();
+        generator1.level.set_value(0.25);
+        topology.add_component(generator1);
+
+        let mut generator2 = ConstantGenerator::default();
+        generator2.level.set_value(0.5);
+        topology.add_component(generator2);
+
+        let test_samples = 48000;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        assert_eq!(obtained, vec![0.75; test_samples]);
+    }
+
+    #[test]
+    fn applies_component_gain() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let mut generator1 = ConstantGenerator::default();
+        generator1.level.set_value(0.5);
+        let generator1_id = topology.add_component(generator1);
+        topology.get_mixer_input_mut(generator1_id).unwrap().gain = 0.5;
+
+        let mut generator2 = ConstantGenerator::default();
+        generator2.level.set_value(-1.0);
+        let generator2_id = topology.add_component(generator2);
+        topology.get_mixer_input_mut(generator2_id).unwrap().gain = 0.25;
+
+        let test_samples = 48000;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        assert_eq!(obtained, vec![0.0; test_samples]);
+    }
+
+    #[test]
+    fn routes_generator_through_effect() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let mut effect = ScalingEffect::default();
+        effect.level.set_value(0.5);
+        let effect_id = topology.add_component(effect);
+
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(0.5);
+        let generator_id = topology.add_component(generator);
+        topology.disconnect_from_output(generator_id);
+
+        topology
+            .connect(generator_id, effect_id, AudioInputIndex(0), 1.0)
+            .unwrap();
+
+        let order: Vec<_> = topology.routing.processing_order().collect();
+        assert_eq!(order, vec![generator_id, effect_id]);
+
+        let test_samples = 48000;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        assert_eq!(obtained, vec![0.25; test_samples]);
+    }
+
+    #[test]
+    fn rejects_routing_cycles() {
+        let (_, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let effect1_id = topology.add_component(ScalingEffect::default());
+        let effect2_id = topology.add_component(ScalingEffect::default());
+        let effect3_id = topology.add_component(ScalingEffect::default());
+
+        topology
+            .connect(effect1_id, effect2_id, AudioInputIndex(0), 1.0)
+            .unwrap();
+        topology
+            .connect(effect2_id, effect3_id, AudioInputIndex(0), 1.0)
+            .unwrap();
+
+        assert_eq!(
+            topology.connect(effect3_id, effect1_id, AudioInputIndex(0), 1.0),
+            Err(RoutingError::Cycle)
+        );
+        assert_eq!(topology.routing.iter_connections().count(), 2);
+    }
+
+    #[test]
+    fn rejects_unknown_inputs() {
+        let (_, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let generator_id = topology.add_component(ConstantGenerator::default());
+        let effect_id = topology.add_component(ScalingEffect::default());
+
+        assert_eq!(
+            topology.connect(effect_id, generator_id, AudioInputIndex(0), 1.0),
+            Err(RoutingError::UnknownInput)
+        );
+        assert_eq!(
+            topology.connect(generator_id, effect_id, AudioInputIndex(1), 1.0),
+            Err(RoutingError::UnknownInput)
+        );
+    }
+
     fn expected_alternating_modulation(audio_level: f32, modulation_amount: f32, samples: usize) -> Vec<f32> {
         (0..samples).map(|s|{
             let s2 = s / 480;
@@ -237,7 +660,7 @@ mod tests {
         let mut generator = ConstantGenerator::default();
         generator.level.set_value(0.1);
 
-        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0));
+        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0)).unwrap();
         generator.level.add_modulation(modulator_id, 0.5);
 
         topology.add_component(generator);
@@ -260,7 +683,7 @@ mod tests {
         let mut generator = ConstantGenerator::default();
         generator.level.set_value(0.1);
 
-        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0));
+        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0)).unwrap();
         generator.level.add_modulation(modulator_id, 0.5);
 
         topology.add_component(generator);
@@ -283,7 +706,7 @@ mod tests {
         let mut generator = ConstantGenerator::default();
         generator.level.set_value(0.1);
 
-        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0));
+        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0)).unwrap();
         generator.level.add_modulation(modulator_id, 0.5);
 
         topology.add_component(generator);
@@ -294,6 +717,64 @@ mod tests {
         assert_eq!(obtained, expected_alternating_modulation(0.1, 0.5, test_samples));
     }
 
+    #[test]
+    fn applies_modulation_at_fractional_period() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(7),
+            128,
+            Channels(1),
+        );
+
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(0.1);
+
+        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0)).unwrap();
+        generator.level.add_modulation(modulator_id, 0.5);
+
+        topology.add_component(generator);
+
+        let test_samples = 96000;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        // 48000 / 7 = 6857.14..., so every seventh period is one sample longer.
+        let expected: Vec<f32> = (0..test_samples)
+            .map(|s| {
+                let ticks_so_far = (7 * (s as u64 + 1) - 1) / 48000 + 1;
+                match ticks_so_far % 2 {
+                    1 => 0.1 + 0.5,
+                    _ => 0.1 - 0.5,
+                }
+            })
+            .collect();
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn applies_modulation_at_exact_long_run_rate() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(44100),
+            ModulationRate(1000),
+            128,
+            Channels(1),
+        );
+
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(0.1);
+
+        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0)).unwrap();
+        generator.level.add_modulation(modulator_id, 0.5);
+
+        topology.add_component(generator);
+
+        let test_samples = 441000;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        // The first tick happens at sample 0, every other one changes the output.
+        let ticks = 1 + obtained.windows(2).filter(|w| w[0] != w[1]).count();
+        assert_eq!(ticks, 10000);
+    }
+
     #[test]
     fn mixes_output() {
         let (mut engine, mut topology) = empty_engine(
@@ -306,7 +787,7 @@ mod tests {
         let mut generator = ConstantGenerator::default();
         generator.level.set_value(0.1);
 
-        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0));
+        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0)).unwrap();
         generator.level.add_modulation(modulator_id, 0.5);
 
         topology.add_component(generator);
@@ -323,4 +804,805 @@ mod tests {
 
         assert_eq!(obtained, expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn mixes_stereo_components() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            480,
+            Channels(2),
+        );
+
+        let mut generator = ConstantStereoGenerator::default();
+        generator.left.set_value(0.25);
+        generator.right.set_value(-0.5);
+        topology.add_component(generator);
+
+        let test_samples = 4800;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        let expected: Vec<f32> = (0..test_samples).flat_map(|_| vec![0.25, -0.5]).collect();
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn pans_mono_components() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            480,
+            Channels(2),
+        );
+
+        let mut generator1 = ConstantGenerator::default();
+        generator1.level.set_value(0.5);
+        let generator1_id = topology.add_component(generator1);
+        topology.get_mixer_input_mut(generator1_id).unwrap().pan = -1.0;
+
+        let mut generator2 = ConstantGenerator::default();
+        generator2.level.set_value(0.25);
+        let generator2_id = topology.add_component(generator2);
+        topology.get_mixer_input_mut(generator2_id).unwrap().pan = 0.5;
+
+        let test_samples = 4800;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        let expected: Vec<f32> = (0..test_samples)
+            .flat_map(|_| vec![0.5 + 0.125, 0.25])
+            .collect();
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn removes_components() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let mut generator1 = ConstantGenerator::default();
+        generator1.level.set_value(0.25);
+        let generator1_id = topology.add_component(generator1);
+
+        let mut generator2 = ConstantGenerator::default();
+        generator2.level.set_value(0.5);
+        topology.add_component(generator2);
+
+        let effect_id = topology.add_component(ScalingEffect::default());
+        topology
+            .connect(generator1_id, effect_id, AudioInputIndex(0), 1.0)
+            .unwrap();
+
+        assert!(topology.remove_component(generator1_id).is_some());
+        assert!(topology.remove_component(generator1_id).is_none());
+        assert!(topology.remove_component(effect_id).is_some());
+        assert_eq!(topology.routing.iter_connections().count(), 0);
+
+        let test_samples = 4800;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        assert_eq!(obtained, vec![0.5; test_samples]);
+    }
+
+    #[test]
+    fn ignores_removed_modulators() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(0.1);
+
+        let stale_modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0)).unwrap();
+        generator.level.add_modulation(stale_modulator_id, 0.5);
+        topology.add_component(generator);
+
+        topology.remove_modulator(stale_modulator_id);
+        let new_modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0)).unwrap();
+        assert_ne!(new_modulator_id, stale_modulator_id);
+
+        let test_samples = 4800;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        assert_eq!(obtained, vec![0.1; test_samples]);
+    }
+
+    #[test]
+    fn smooths_modulation_across_blocks() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            100,
+            Channels(1),
+        );
+
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(0.1);
+        generator
+            .level
+            .set_smoothing(Smoothing::Linear(engine.spec.modulation_period));
+
+        let modulator_id = topology.add_modulator(AlternatingModulator::new(-1.0)).unwrap();
+        generator.level.add_modulation(modulator_id, 0.5);
+
+        topology.add_component(generator);
+
+        let test_samples = 4800;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        let targets = expected_alternating_modulation(0.1, 0.5, test_samples);
+        let mut ramp_start_value = 0.1;
+        let expected: Vec<f32> = (0..test_samples)
+            .map(|s| {
+                let progress = (s % 480) as f32 / 480.0;
+                if s % 480 == 0 && s > 0 {
+                    ramp_start_value = targets[s - 1];
+                }
+                ramp_start_value + (targets[s] - ramp_start_value) * progress
+            })
+            .collect();
+
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn modulates_modulators_in_dependency_order() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let follower_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        let source_id = topology
+            .add_modulator(AlternatingModulator::new(-1.0))
+            .unwrap();
+        assert_eq!(topology.modulation_order, vec![follower_id, source_id]);
+
+        topology.set_name(follower_id, "follower");
+        let follower_level = topology.find_parameter("follower.level").unwrap();
+        topology
+            .add_modulation(
+                follower_level,
+                source_id,
+                0.25,
+                ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Linear),
+            )
+            .unwrap();
+        assert_eq!(topology.modulation_order, vec![source_id, follower_id]);
+
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(0.0);
+        generator.level.add_modulation(follower_id, 1.0);
+        topology.add_component(generator);
+
+        let test_samples = 48000;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        assert_eq!(
+            obtained,
+            expected_alternating_modulation(0.0, 0.5, test_samples)
+        );
+    }
+
+    #[test]
+    fn rejects_modulation_cycles() {
+        let (_, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+        let follower1_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        let follower2_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        topology.set_name(follower1_id, "follower1");
+        topology.set_name(follower2_id, "follower2");
+        let level1 = topology.find_parameter("follower1.level").unwrap();
+        let level2 = topology.find_parameter("follower2.level").unwrap();
+        let mapping = ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Linear);
+
+        topology
+            .add_modulation(level1, follower2_id, 0.5, mapping)
+            .unwrap();
+        assert_eq!(
+            topology.add_modulation(level2, follower1_id, 0.5, mapping),
+            Err(Error::Modulation(ModulationError::Cycle))
+        );
+        assert_eq!(
+            topology.add_modulation(level1, follower1_id, 0.5, mapping),
+            Err(Error::Modulation(ModulationError::Cycle))
+        );
+
+        let modulators1: Vec<_> = topology
+            .get_parameter(level1)
+            .unwrap()
+            .iter_modulators()
+            .collect();
+        let modulators2: Vec<_> = topology
+            .get_parameter(level2)
+            .unwrap()
+            .iter_modulators()
+            .collect();
+        assert_eq!(modulators1, vec![follower2_id]);
+        assert!(modulators2.is_empty());
+        assert_eq!(topology.modulation_order, vec![follower2_id, follower1_id]);
+    }
+
+    #[test]
+    fn applies_automation_steps_between_blocks() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_automation(
+            AutomationLane::default()
+                .with_breakpoint(AudioSampleIndex(0), 0.25, Segment::Step)
+                .with_breakpoint(AudioSampleIndex(1000), 0.75, Segment::Step),
+        );
+        topology.add_component(generator);
+
+        let test_samples = 4800;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        let expected: Vec<f32> = (0..test_samples)
+            .map(|s| if s < 1000 { 0.25 } else { 0.75 })
+            .collect();
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn applies_linear_automation_per_sample() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_automation(
+            AutomationLane::default()
+                .with_breakpoint(AudioSampleIndex(100), 0.0, Segment::Step)
+                .with_breakpoint(AudioSampleIndex(1100), 1.0, Segment::Linear),
+        );
+        topology.add_component(generator);
+
+        let test_samples = 1500;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        let expected: Vec<f32> = (0..test_samples)
+            .map(|s| match s {
+                s if s < 100 => 0.0,
+                s if s < 1100 => (s - 100) as f32 / 1000.0,
+                _ => 1.0,
+            })
+            .collect();
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn applies_commands_at_block_start() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(0.25);
+        let generator_id = topology.add_component(generator);
+        topology.set_name(generator_id, "gen1");
+        let level = topology.find_parameter("gen1.level").unwrap();
+
+        let (mut sender, receiver) = command_queue(16);
+        engine.set_command_receiver(receiver);
+
+        let mut obtained = run_engine(&mut engine, &mut topology, 128);
+        sender
+            .send(Command::SetParameter {
+                parameter: level,
+                value: 0.5,
+            })
+            .unwrap();
+        obtained.extend(run_engine(&mut engine, &mut topology, 128));
+
+        let mut expected = vec![0.25; 128];
+        expected.extend(vec![0.5; 128]);
+        assert_eq!(obtained, expected);
+        assert_eq!(sender.pop_error(), None);
+    }
+
+    #[test]
+    fn reports_failed_commands() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let generator_id = topology.add_component(ConstantGenerator::default());
+        topology.set_name(generator_id, "gen1");
+        let level = topology.find_parameter("gen1.level").unwrap();
+
+        let (mut sender, receiver) = command_queue(16);
+        engine.set_command_receiver(receiver);
+
+        sender
+            .send(Command::SetParameter {
+                parameter: level,
+                value: 2.0,
+            })
+            .unwrap();
+        run_engine(&mut engine, &mut topology, 128);
+
+        topology.remove_component(generator_id);
+        sender
+            .send(Command::SetParameter {
+                parameter: level,
+                value: 0.5,
+            })
+            .unwrap();
+        sender
+            .send(Command::SetBypass {
+                component: generator_id,
+                bypassed: true,
+            })
+            .unwrap();
+        run_engine(&mut engine, &mut topology, 128);
+
+        assert_eq!(
+            sender.pop_error(),
+            Some(Error::ParameterOutOfRange {
+                value: 2.0,
+                minimum_value: -1.0,
+                maximum_value: 1.0,
+            })
+        );
+        assert_eq!(sender.pop_error(), Some(Error::UnknownParameter));
+        assert_eq!(sender.pop_error(), Some(Error::UnknownComponent));
+        assert_eq!(sender.pop_error(), None);
+    }
+
+    #[test]
+    fn bypasses_components() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        let mut effect = ScalingEffect::default();
+        effect.level.set_value(0.5);
+        let effect_id = topology.add_component(effect);
+
+        let mut generator1 = ConstantGenerator::default();
+        generator1.level.set_value(0.5);
+        let generator1_id = topology.add_component(generator1);
+        topology.disconnect_from_output(generator1_id);
+        topology
+            .connect(generator1_id, effect_id, AudioInputIndex(0), 1.0)
+            .unwrap();
+
+        let mut generator2 = ConstantGenerator::default();
+        generator2.level.set_value(0.125);
+        let generator2_id = topology.add_component(generator2);
+
+        let (mut sender, receiver) = command_queue(16);
+        engine.set_command_receiver(receiver);
+        for component in [effect_id, generator2_id] {
+            sender
+                .send(Command::SetBypass {
+                    component,
+                    bypassed: true,
+                })
+                .unwrap();
+        }
+
+        let test_samples = 4800;
+        let obtained = run_engine(&mut engine, &mut topology, test_samples);
+
+        assert_eq!(obtained, vec![0.5; test_samples]);
+    }
+
+    fn constant_topology(engine: &Engine, level: f32) -> AudioTopology {
+        let mut topology = engine.create_empty_topology();
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(level);
+        topology.add_component(generator);
+        topology
+    }
+
+    #[test]
+    fn crossfades_between_topologies() {
+        let (mut engine, _) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+        let mut topology = constant_topology(&engine, 0.5);
+
+        let (mut sender, receiver) = topology_swap_queue(engine.spec, 4);
+        engine.set_topology_receiver(receiver);
+
+        let mut obtained = run_engine(&mut engine, &mut topology, 128);
+        sender
+            .send(constant_topology(&engine, 0.1), AudioSampleDifference(256))
+            .unwrap();
+        obtained.extend(run_engine(&mut engine, &mut topology, 128));
+        assert_eq!(sender.collect_retired(), 0);
+        obtained.extend(run_engine(&mut engine, &mut topology, 256));
+        assert_eq!(sender.collect_retired(), 1);
+
+        let expected: Vec<f32> = (0..512)
+            .map(|s| match s {
+                s if s < 128 => 0.5,
+                s if s < 384 => {
+                    let fade_in = (s - 128) as f32 / 256.0;
+                    0.1 * fade_in + 0.5 * (1.0 - fade_in)
+                }
+                _ => 0.1,
+            })
+            .collect();
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn swaps_topologies_without_crossfade() {
+        let (mut engine, _) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+        let mut topology = constant_topology(&engine, 0.5);
+
+        let (mut sender, receiver) = topology_swap_queue(engine.spec, 4);
+        engine.set_topology_receiver(receiver);
+
+        sender
+            .send(constant_topology(&engine, 0.1), AudioSampleDifference(0))
+            .unwrap();
+        let obtained = run_engine(&mut engine, &mut topology, 128);
+
+        assert_eq!(obtained, vec![0.1; 128]);
+        assert_eq!(sender.collect_retired(), 1);
+    }
+
+    #[test]
+    fn rejects_incompatible_topologies() {
+        let (engine, _) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+        let (_, stereo_topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(2),
+        );
+
+        let (mut sender, _) = topology_swap_queue(engine.spec, 4);
+
+        assert_eq!(
+            sender
+                .send(stereo_topology, AudioSampleDifference(0))
+                .err(),
+            Some(Error::IncompatibleTopology)
+        );
+    }
+
+    #[test]
+    fn starts_notes_at_event_offsets() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+        let generator_id = topology.add_component(NoteGenerator::default());
+
+        let events = [
+            TimedCommand {
+                offset: AudioSampleDifference(50),
+                command: Command::NoteOn {
+                    component: generator_id,
+                    note: 50,
+                    velocity: 127,
+                },
+            },
+            TimedCommand {
+                offset: AudioSampleDifference(100),
+                command: Command::NoteOff {
+                    component: generator_id,
+                    note: 50,
+                },
+            },
+        ];
+        let mut obtained = vec![f32::NAN; 128];
+        engine.advance_with_events(&mut topology, &mut obtained, &events);
+
+        let level = 50.0 / 100.0 * 127.0 / 127.0;
+        let expected: Vec<f32> = (0..128)
+            .map(|s| if (50..100).contains(&s) { level } else { 0.0 })
+            .collect();
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn applies_parameter_events_across_modulation_boundaries() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            1000,
+            Channels(1),
+        );
+        let generator_id = topology.add_component(ConstantGenerator::default());
+        topology.set_name(generator_id, "gen1");
+        let level = topology.find_parameter("gen1.level").unwrap();
+
+        let set_level = |offset, value| TimedCommand {
+            offset: AudioSampleDifference(offset),
+            command: Command::SetParameter {
+                parameter: level,
+                value,
+            },
+        };
+        let events = [set_level(0, 0.25), set_level(300, 0.5), set_level(700, 0.75)];
+        let mut obtained = vec![f32::NAN; 1000];
+        engine.advance_with_events(&mut topology, &mut obtained, &events);
+
+        let expected: Vec<f32> = (0..1000)
+            .map(|s| match s {
+                s if s < 300 => 0.25,
+                s if s < 700 => 0.5,
+                _ => 0.75,
+            })
+            .collect();
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        let invalid = |field, value| Some(Error::InvalidEngine { field, value });
+        let spec = |sampling_rate, modulation_rate, channels, step| {
+            EngineSpec::try_new(
+                SamplingRate(sampling_rate),
+                ModulationRate(modulation_rate),
+                Channels(channels),
+                step,
+            )
+            .err()
+        };
+
+        assert_eq!(spec(0, 100, 1, 128), invalid("sampling_rate", 0));
+        assert_eq!(spec(48000, 0, 1, 128), invalid("modulation_rate", 0));
+        assert_eq!(spec(48000, 96000, 1, 128), invalid("modulation_rate", 96000));
+        assert_eq!(spec(48000, 100, 0, 128), invalid("channels", 0));
+        assert_eq!(spec(48000, 100, 1, 0), invalid("max_samples_per_step", 0));
+        assert_eq!(spec(48000, 48000, 1, 1), None);
+    }
+
+    #[test]
+    fn splits_oversized_blocks() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(2),
+        );
+        let generator_id = topology.add_component(ConstantGenerator::default());
+        topology.set_name(generator_id, "gen1");
+        let level = topology.find_parameter("gen1.level").unwrap();
+
+        let set_level = |offset, value| TimedCommand {
+            offset: AudioSampleDifference(offset),
+            command: Command::SetParameter {
+                parameter: level,
+                value,
+            },
+        };
+        // The events land before, on and after the sub-block boundaries at 128 and 256.
+        let events = [
+            set_level(100, 0.25),
+            set_level(128, 0.5),
+            set_level(290, 0.75),
+        ];
+        let mut obtained = vec![f32::NAN; 2 * 300];
+        engine.advance_with_events(&mut topology, &mut obtained, &events);
+
+        assert_eq!(engine.current_sample(), AudioSampleIndex(300));
+        let expected: Vec<f32> = (0..300)
+            .flat_map(|s| {
+                let level = match s {
+                    s if s < 100 => 1.0,
+                    s if s < 128 => 0.25,
+                    s if s < 290 => 0.5,
+                    _ => 0.75,
+                };
+                vec![level, level]
+            })
+            .collect();
+        assert_eq!(obtained, expected);
+    }
+
+    // Callers hold on to later events until the block they fall in, like `MidiPlayer` does.
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "events have to fall within the block")]
+    fn rejects_events_past_the_end_of_the_block() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+        let generator_id = topology.add_component(ConstantGenerator::default());
+        let events = [TimedCommand {
+            offset: AudioSampleDifference(128),
+            command: Command::NoteOn {
+                component: generator_id,
+                note: 50,
+                velocity: 127,
+            },
+        }];
+
+        let mut obtained = vec![f32::NAN; 128];
+        engine.advance_with_events(&mut topology, &mut obtained, &events);
+    }
+
+    #[test]
+    fn crossfades_across_oversized_blocks() {
+        let (mut engine, _) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+        let mut topology = constant_topology(&engine, 0.5);
+
+        let (mut sender, receiver) = topology_swap_queue(engine.spec, 4);
+        engine.set_topology_receiver(receiver);
+        sender
+            .send(constant_topology(&engine, 0.1), AudioSampleDifference(256))
+            .unwrap();
+
+        let mut obtained = vec![f32::NAN; 384];
+        engine.advance(&mut topology, &mut obtained);
+        assert_eq!(sender.collect_retired(), 1);
+
+        let expected: Vec<f32> = (0..384)
+            .map(|s| match s {
+                s if s < 256 => {
+                    let fade_in = s as f32 / 256.0;
+                    0.1 * fade_in + 0.5 * (1.0 - fade_in)
+                }
+                _ => 0.1,
+            })
+            .collect();
+        assert_eq!(obtained, expected);
+    }
+
+    #[test]
+    fn reports_missing_modulators_once() {
+        let (mut engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+        let (mut sender, receiver) = command_queue(16);
+        engine.set_command_receiver(receiver);
+
+        let modulator_id = topology
+            .add_modulator(AlternatingModulator::new(-1.0))
+            .unwrap();
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(0.1);
+        generator.level.add_modulation(modulator_id, 0.5);
+        let generator_id = topology.add_component(generator);
+
+        // Bypasses the topology, which would clean up the modulation.
+        topology
+            .modulation_components
+            .remove_component(modulator_id);
+        topology.update_modulation_order().unwrap();
+
+        let obtained = run_engine(&mut engine, &mut topology, 4800);
+
+        assert_eq!(obtained, vec![0.1; 4800]);
+        assert_eq!(sender.pop_error(), Some(Error::UnknownModulator));
+        assert_eq!(sender.pop_error(), None);
+        let generator = topology
+            .audio_components
+            .get_component(generator_id)
+            .unwrap();
+        assert_eq!(generator.parameter(0).unwrap().iter_modulators().count(), 1);
+    }
+
+    fn modulated_engine(modulators: usize) -> (Engine, AudioTopology) {
+        let (engine, mut topology) = empty_engine(
+            SamplingRate(48000),
+            ModulationRate(100),
+            128,
+            Channels(1),
+        );
+
+        for _ in 0..modulators {
+            let modulator_id = topology
+                .add_modulator(AlternatingModulator::new(0.5))
+                .unwrap();
+            let mut generator = ConstantGenerator::default();
+            generator.level.add_modulation(modulator_id, 0.001);
+            topology.add_component(generator);
+        }
+
+        (engine, topology)
+    }
+
+    fn modulation_probes(modulators: usize) -> usize {
+        let (mut engine, mut topology) = modulated_engine(modulators);
+        take_probes();
+
+        run_engine(&mut engine, &mut topology, 4800);
+        take_probes()
+    }
+
+    #[test]
+    fn modulation_lookups_do_not_grow_with_modulator_count() {
+        let small_patch = modulation_probes(10);
+        let large_patch = modulation_probes(500);
+
+        assert!(small_patch > 0);
+        assert_eq!(small_patch / 10, large_patch / 500);
+    }
+
+    // Wall-clock numbers are too noisy for CI, run with --ignored on an idle machine.
+    #[test]
+    #[ignore]
+    fn modulation_time_does_not_grow_with_modulator_count() {
+        let time_per_modulator = |modulators: usize| {
+            let (mut engine, mut topology) = modulated_engine(modulators);
+            (0..5)
+                .map(|_| {
+                    let start = Instant::now();
+                    run_engine(&mut engine, &mut topology, 4800);
+                    start.elapsed() / modulators as u32
+                })
+                .min()
+                .unwrap()
+        };
+
+        let small_patch = time_per_modulator(10);
+        let large_patch = time_per_modulator(500);
+
+        assert!(
+            large_patch < small_patch * 4,
+            "small patch: {:?}, large patch: {:?}",
+            small_patch,
+            large_patch
+        );
+    }
+}
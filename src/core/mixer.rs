@@ -0,0 +1,153 @@
+use crate::core::buffers::{MultiChannelSlice, MultiChannelSliceMut};
+use crate::core::topology::AudioComponentId;
+
+pub struct MixerInput {
+    pub component: AudioComponentId,
+    pub gain: f32,
+    pub pan: f32,
+}
+
+#[derive(Default)]
+pub struct Mixer {
+    inputs: Vec<MixerInput>,
+}
+
+impl Mixer {
+    pub fn add_input(&mut self, component: AudioComponentId, gain: f32) {
+        self.inputs.push(MixerInput {
+            component,
+            gain,
+            pan: 0.0,
+        });
+    }
+
+    pub fn remove_input(&mut self, component: AudioComponentId) {
+        self.inputs.retain(|i| i.component != component);
+    }
+
+    pub fn get_input_mut(&mut self, component: AudioComponentId) -> Option<&mut MixerInput> {
+        self.inputs.iter_mut().find(|i| i.component == component)
+    }
+
+    pub fn iter_inputs(&self) -> impl Iterator<Item = &MixerInput> {
+        self.inputs.iter()
+    }
+}
+
+pub fn accumulate(output: &mut [f32], input: &[f32], gain: f32) {
+    for (o, i) in output.iter_mut().zip(input.iter()) {
+        *o += *i * gain;
+    }
+}
+
+// Pan only applies to stereo outputs, where it works as a balance control for mono and stereo
+// sources alike: the centre leaves both sides at unity and panning attenuates the opposite side
+// linearly, down to silence at the extremes. It isn't a constant power pan law, so a centred mono
+// source sounds louder than a hard-panned one. Other output layouts ignore it.
+pub fn balance_gains(pan: f32) -> [f32; 2] {
+    let pan = pan.clamp(-1.0, 1.0);
+    [(1.0 - pan).min(1.0), (1.0 + pan).min(1.0)]
+}
+
+// Inputs with fewer channels than the output wrap around, so mono reaches every output channel.
+// Inputs with more channels are folded down: output channel c averages the input channels
+// c, c + outputs, c + 2 * outputs... so e.g. stereo into mono becomes (left + right) / 2. Inputs
+// without channels add nothing.
+pub fn mix_channels(
+    output: &mut MultiChannelSliceMut,
+    input: &MultiChannelSlice,
+    gain: f32,
+    pan: f32,
+) {
+    let output_channels = output.channels().0 as usize;
+    let input_channels = input.channels().0 as usize;
+    let balance = balance_gains(pan);
+    if input_channels == 0 {
+        return;
+    }
+
+    for channel in 0..output_channels {
+        let channel_gain = match output_channels {
+            2 => gain * balance[channel % 2],
+            _ => gain,
+        };
+
+        if input_channels <= output_channels {
+            accumulate(
+                output.channel_mut(channel),
+                input.channel(channel % input_channels),
+                channel_gain,
+            );
+            continue;
+        }
+
+        let folded = (channel..input_channels).step_by(output_channels);
+        let downmix_gain = channel_gain / folded.len() as f32;
+        for input_channel in folded {
+            accumulate(
+                output.channel_mut(channel),
+                input.channel(input_channel),
+                downmix_gain,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::buffers::MultiChannelBuffer;
+    use crate::core::concepts::Channels;
+
+    fn buffer_with(channels: &[f32]) -> MultiChannelBuffer {
+        let mut buffer = MultiChannelBuffer::new(Channels(channels.len() as u16), 4);
+        let mut slice = buffer.slice_mut(0..4);
+        for (channel, value) in channels.iter().enumerate() {
+            slice.channel_mut(channel).fill(*value);
+        }
+        buffer
+    }
+
+    fn mix(output_channels: u16, input: &[f32], gain: f32, pan: f32) -> Vec<f32> {
+        let input = buffer_with(input);
+        let mut output = MultiChannelBuffer::new(Channels(output_channels), 4);
+        let mut output_slice = output.slice_mut(0..4);
+        output_slice.fill(0.0);
+        mix_channels(&mut output_slice, &input.slice(0..4), gain, pan);
+
+        (0..output_channels as usize)
+            .map(|c| output.slice(0..4).channel(c)[0])
+            .collect()
+    }
+
+    #[test]
+    fn downmixes_stereo_into_mono() {
+        assert_eq!(mix(1, &[0.5, -0.25], 1.0, 0.0), vec![0.125]);
+        assert_eq!(mix(1, &[0.5, 0.25], 0.5, 0.0), vec![0.1875]);
+    }
+
+    #[test]
+    fn folds_extra_channels_onto_the_output() {
+        assert_eq!(mix(2, &[0.5, 0.25, 0.25, 0.75], 1.0, 0.0), vec![0.375, 0.5]);
+        assert_eq!(mix(2, &[0.5, 0.25, 0.25], 1.0, 0.0), vec![0.375, 0.25]);
+    }
+
+    #[test]
+    fn skips_inputs_without_channels() {
+        assert_eq!(mix(2, &[], 1.0, 0.0), vec![0.0, 0.0]);
+        assert_eq!(mix(1, &[], 1.0, 0.0), vec![0.0]);
+    }
+
+    #[test]
+    fn spreads_mono_into_every_channel() {
+        assert_eq!(mix(3, &[0.5], 1.0, 0.0), vec![0.5, 0.5, 0.5]);
+        assert_eq!(mix(3, &[0.5], 1.0, 1.0), vec![0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn balances_stereo_outputs() {
+        assert_eq!(mix(2, &[0.5], 1.0, 0.0), vec![0.5, 0.5]);
+        assert_eq!(mix(2, &[0.5], 1.0, 0.5), vec![0.25, 0.5]);
+        assert_eq!(mix(2, &[0.5, 0.25], 1.0, -1.0), vec![0.5, 0.0]);
+    }
+}
@@ -1,14 +1,28 @@
+pub mod automation;
 pub mod buffers;
+pub mod commands;
 pub mod component_store;
 pub mod concepts;
 pub mod engine;
+pub mod mixer;
+pub mod modulation;
 pub mod parameter;
+pub mod reflection;
+pub mod routing;
 pub mod topology;
+pub mod topology_swap;
 pub mod traits;
 
+pub use automation::*;
 pub use buffers::*;
+pub use commands::*;
 pub use concepts::*;
 pub use engine::*;
+pub use mixer::*;
+pub use modulation::*;
 pub use parameter::*;
+pub use reflection::*;
+pub use routing::*;
 pub use topology::*;
+pub use topology_swap::*;
 pub use traits::*;
@@ -0,0 +1,113 @@
+use crate::core::topology::{ModulationComponentId, ModulationComponentsStore};
+use crate::core::traits::ModulationComponent;
+use std::fmt;
+
+#[derive(PartialEq, Debug)]
+pub enum ModulationError {
+    Cycle,
+}
+
+impl fmt::Display for ModulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModulationError::Cycle => write!(f, "modulators form a cycle"),
+        }
+    }
+}
+
+impl std::error::Error for ModulationError {}
+
+// Room for ordering modulators, reserved off the audio thread so modulation commands can reorder
+// them without allocating. Dependents are kept per slot as ranges of `dependents`.
+#[derive(Default)]
+pub struct ModulationOrderScratch {
+    in_degrees: Vec<usize>,
+    dependents_start: Vec<usize>,
+    dependents: Vec<usize>,
+}
+
+impl ModulationOrderScratch {
+    pub fn reserve(&mut self, slots: usize, modulations: usize) {
+        reserve_total(&mut self.in_degrees, slots);
+        reserve_total(&mut self.dependents_start, slots + 1);
+        reserve_total(&mut self.dependents, modulations);
+    }
+}
+
+fn reserve_total<T>(buffer: &mut Vec<T>, total: usize) {
+    buffer.reserve(total.saturating_sub(buffer.len()));
+}
+
+// Kahn's algorithm, with `order` doubling as the queue of modulators whose sources are all
+// ordered. Nothing is allocated while `order` and the scratch have room for every modulator and
+// modulation.
+pub fn compute_modulation_order(
+    modulators: &ModulationComponentsStore,
+    scratch: &mut ModulationOrderScratch,
+    order: &mut Vec<ModulationComponentId>,
+) -> Result<(), ModulationError> {
+    let slots = modulators.slot_count();
+    let in_degrees = &mut scratch.in_degrees;
+    let starts = &mut scratch.dependents_start;
+    in_degrees.clear();
+    in_degrees.resize(slots, 0);
+    starts.clear();
+    starts.resize(slots + 1, 0);
+
+    // Counts the dependents of every slot, then turns the counts into the ends of their ranges.
+    for id in modulators.iter_ids() {
+        for source in sources(modulators.get_component(id).unwrap()) {
+            if modulators.contains(source) {
+                in_degrees[id.index()] += 1;
+                starts[source.index()] += 1;
+            }
+        }
+    }
+    let mut total = 0;
+    for start in starts.iter_mut() {
+        total += *start;
+        *start = total;
+    }
+
+    // Filling each range from its end leaves `starts` at the range starts.
+    scratch.dependents.clear();
+    scratch.dependents.resize(total, 0);
+    for id in modulators.iter_ids() {
+        for source in sources(modulators.get_component(id).unwrap()) {
+            if modulators.contains(source) {
+                starts[source.index()] -= 1;
+                scratch.dependents[starts[source.index()]] = id.index();
+            }
+        }
+    }
+
+    order.clear();
+    order.extend(
+        modulators
+            .iter_ids()
+            .filter(|id| in_degrees[id.index()] == 0),
+    );
+    let mut next = 0;
+    while let Some(id) = order.get(next).copied() {
+        next += 1;
+        for dependent in &scratch.dependents[starts[id.index()]..starts[id.index() + 1]] {
+            in_degrees[*dependent] -= 1;
+            if in_degrees[*dependent] == 0 {
+                order.push(modulators.id_at(*dependent).unwrap());
+            }
+        }
+    }
+
+    match order.len() == modulators.len() {
+        true => Ok(()),
+        false => Err(ModulationError::Cycle),
+    }
+}
+
+fn sources(
+    modulator: &dyn ModulationComponent,
+) -> impl Iterator<Item = ModulationComponentId> + '_ {
+    (0..modulator.parameter_descriptors().len())
+        .filter_map(move |index| modulator.parameter(index))
+        .flat_map(|parameter| parameter.iter_modulators())
+}
@@ -1,39 +1,182 @@
+use crate::core::automation::AutomationLane;
+use crate::core::concepts::{AudioSampleDifference, AudioSampleIndex};
 use crate::core::topology::ModulationComponentId;
 use crate::core::ModulationComponentsStore;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone)]
+pub enum Smoothing {
+    None,
+    Linear(AudioSampleDifference),
+    Exponential(AudioSampleDifference),
+}
+
+const CURVE_STEEPNESS: f32 = 4.0;
+// Modulations added while the engine runs have to fit in what's reserved up front.
+pub const MODULATION_CAPACITY: usize = 8;
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Polarity {
+    Unipolar,
+    Bipolar,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModulationCurve {
+    Linear,
+    Exponential,
+    Logarithmic,
+}
+
+impl ModulationCurve {
+    fn apply(&self, x: f32) -> f32 {
+        let magnitude = x.abs();
+        let shaped = match self {
+            ModulationCurve::Linear => return x,
+            ModulationCurve::Exponential => {
+                (CURVE_STEEPNESS * magnitude).exp_m1() / CURVE_STEEPNESS.exp_m1()
+            }
+            ModulationCurve::Logarithmic => {
+                (CURVE_STEEPNESS.exp_m1() * magnitude).ln_1p() / CURVE_STEEPNESS
+            }
+        };
+        shaped.copysign(x)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModulationUnit {
+    Range,
+    Octaves,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ModulationMapping {
+    pub polarity: Polarity,
+    pub curve: ModulationCurve,
+    pub unit: ModulationUnit,
+    pub offset: f32,
+}
+
+impl ModulationMapping {
+    pub fn range(polarity: Polarity, curve: ModulationCurve) -> Self {
+        Self {
+            polarity,
+            curve,
+            unit: ModulationUnit::Range,
+            offset: 0.0,
+        }
+    }
+
+    pub fn octaves(polarity: Polarity, curve: ModulationCurve) -> Self {
+        Self {
+            polarity,
+            curve,
+            unit: ModulationUnit::Octaves,
+            offset: 0.0,
+        }
+    }
+
+    pub fn with_offset(self, offset: f32) -> Self {
+        Self { offset, ..self }
+    }
+
+    fn map(&self, modulator_level: f32, level: f32, range: f32) -> f32 {
+        let normalized = match self.polarity {
+            Polarity::Bipolar => modulator_level,
+            Polarity::Unipolar => (modulator_level + 1.0) / 2.0,
+        };
+        let shaped = self.curve.apply(normalized);
+
+        match self.unit {
+            ModulationUnit::Range => shaped * range * level + self.offset,
+            ModulationUnit::Octaves => shaped * level + self.offset,
+        }
+    }
+}
 
 pub struct Modulation {
     modulator: ModulationComponentId,
     level: f32,
+    mapping: ModulationMapping,
     result: f32,
+    missing: bool,
 }
 
 pub struct Parameter {
     value: f32,
+    default_value: f32,
     minimum_value: f32,
     maximum_value: f32,
     modulations: Vec<Modulation>,
     total_modulation: f32,
+    total_octaves: f32,
     final_value: f32,
+    smoothing: Smoothing,
+    ramp_start: AudioSampleIndex,
+    ramp_start_value: f32,
+    automation: Option<AutomationLane>,
 }
 
 impl Parameter {
     pub fn new(value: f32, minimum_value: f32, maximum_value: f32) -> Self {
-        assert!(minimum_value <= value && value <= maximum_value);
+        match Self::try_new(value, minimum_value, maximum_value) {
+            Ok(parameter) => parameter,
+            Err(e) => panic!("{}", e),
+        }
+    }
 
-        Self {
+    pub fn try_new(value: f32, minimum_value: f32, maximum_value: f32) -> Result<Self> {
+        check_range(value, minimum_value, maximum_value)?;
+
+        Ok(Self {
             value,
+            default_value: value,
             minimum_value,
             maximum_value,
-            modulations: vec![],
+            modulations: Vec::with_capacity(MODULATION_CAPACITY),
             total_modulation: 0.0,
+            total_octaves: 0.0,
             final_value: value,
-        }
+            smoothing: Smoothing::None,
+            ramp_start: AudioSampleIndex(0),
+            ramp_start_value: value,
+            automation: None,
+        })
     }
 
     pub fn set_value(&mut self, value: f32) {
-        assert!(self.minimum_value <= value && value <= self.maximum_value);
+        if let Err(e) = self.try_set_value(value) {
+            panic!("{}", e);
+        }
+    }
+
+    pub fn try_set_value(&mut self, value: f32) -> Result<()> {
+        check_range(value, self.minimum_value, self.maximum_value)?;
         self.value = value;
         self.update_final_value();
+        self.ramp_start_value = self.final_value;
+        Ok(())
+    }
+
+    pub fn default_value(&self) -> f32 {
+        self.default_value
+    }
+
+    pub fn minimum_value(&self) -> f32 {
+        self.minimum_value
+    }
+
+    pub fn maximum_value(&self) -> f32 {
+        self.maximum_value
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothing = smoothing;
     }
 
     pub fn get_value(&self) -> f32 {
@@ -44,33 +187,375 @@ impl Parameter {
         self.final_value
     }
 
+    // Automated parameters follow their lane sample by sample, so smoothing doesn't apply to them.
+    pub fn value_at(&self, sample: AudioSampleIndex) -> f32 {
+        if let Some(value) = self.automation.as_ref().and_then(|a| a.value_at(sample)) {
+            return self.modulated(self.clamp(value));
+        }
+
+        let (ramp_length, exponential) = match self.smoothing {
+            Smoothing::None => return self.final_value,
+            Smoothing::Linear(length) => (length, false),
+            Smoothing::Exponential(length) => (length, true),
+        };
+
+        if sample.0 < self.ramp_start.0 {
+            return self.ramp_start_value;
+        }
+        let elapsed = sample - self.ramp_start;
+        if elapsed >= ramp_length {
+            return self.final_value;
+        }
+
+        let start = self.ramp_start_value;
+        let end = self.final_value;
+        let progress = elapsed.0 as f32 / ramp_length.0 as f32;
+
+        // An exponential ramp can't cross or touch zero, so it falls back to linear there.
+        if exponential && start * end > 0.0 {
+            start * (end / start).powf(progress)
+        } else {
+            start + (end - start) * progress
+        }
+    }
+
+    // Maps the whole modulator swing onto the parameter range, starting at its minimum. Panics
+    // when the parameter has no room left, like `set_value` does for values out of range.
     pub fn add_modulation(&mut self, modulator: ModulationComponentId, level: f32) {
+        let mapping = ModulationMapping::range(Polarity::Unipolar, ModulationCurve::Linear)
+            .with_offset(self.minimum_value * level);
+        if let Err(e) = self.add_mapped_modulation(modulator, level, mapping) {
+            panic!("{}", e);
+        }
+    }
+
+    // Never grows past the reserved capacity, so the audio thread can add modulations too.
+    pub fn add_mapped_modulation(
+        &mut self,
+        modulator: ModulationComponentId,
+        level: f32,
+        mapping: ModulationMapping,
+    ) -> Result<()> {
+        if self.modulations.len() >= MODULATION_CAPACITY {
+            return Err(Error::TooManyModulations);
+        }
+
         let modulation = Modulation {
             modulator,
             level,
+            mapping,
             result: 0.0,
+            missing: false,
         };
 
         self.modulations.push(modulation);
+        Ok(())
     }
 
-    pub fn apply_modulations(&mut self, modulators: &ModulationComponentsStore) {
-        let min = self.minimum_value;
-        let max = self.maximum_value;
-        let map_modulation_domain = |m| (m + 1.0) / 2.0 * (max - min) + min;
+    pub fn set_automation(&mut self, automation: AutomationLane) {
+        self.automation = Some(automation);
+    }
+
+    pub fn clear_automation(&mut self) {
+        self.automation = None;
+    }
+
+    pub fn automation(&self) -> Option<&AutomationLane> {
+        self.automation.as_ref()
+    }
+
+    pub fn apply_automation(&mut self, sample: AudioSampleIndex) {
+        if let Some(value) = self.automation.as_ref().and_then(|a| a.value_at(sample)) {
+            self.value = self.clamp(value);
+            self.update_final_value();
+            self.ramp_start_value = self.final_value;
+        }
+    }
+
+    pub fn next_automation_breakpoint(&self, sample: AudioSampleIndex) -> Option<AudioSampleIndex> {
+        self.automation
+            .as_ref()
+            .and_then(|a| a.next_breakpoint_after(sample))
+    }
+
+    pub fn iter_modulators(&self) -> impl Iterator<Item = ModulationComponentId> + '_ {
+        self.modulations.iter().map(|m| m.modulator)
+    }
+
+    pub fn iter_modulations(
+        &self,
+    ) -> impl Iterator<Item = (ModulationComponentId, f32, ModulationMapping)> + '_ {
+        self.modulations
+            .iter()
+            .map(|m| (m.modulator, m.level, m.mapping))
+    }
+
+    pub fn remove_modulation(&mut self, modulator: ModulationComponentId) {
+        self.modulations.retain(|m| m.modulator != modulator);
+    }
+
+    // Modulations whose modulator can't be found contribute nothing but are kept, e.g. a modulator
+    // that's taken out while it's being processed comes back later. Each one is reported when it
+    // goes missing rather than on every call.
+    pub fn apply_modulations(
+        &mut self,
+        modulators: &ModulationComponentsStore,
+        sample: AudioSampleIndex,
+    ) -> Result<()> {
+        self.ramp_start_value = self.value_at(sample);
+        self.ramp_start = sample;
+
+        let range = self.maximum_value - self.minimum_value;
+        let mut result = Ok(());
 
         for modulation in self.modulations.iter_mut() {
-            let modulator = modulators.get_component(modulation.modulator).unwrap();
-            modulation.result =
-                map_modulation_domain(modulator.get_current_level()) * modulation.level;
+            match modulators.get_component(modulation.modulator) {
+                Some(modulator) => {
+                    modulation.result = modulation.mapping.map(
+                        modulator.get_current_level(),
+                        modulation.level,
+                        range,
+                    );
+                    modulation.missing = false;
+                }
+                None => {
+                    modulation.result = 0.0;
+                    if !modulation.missing {
+                        modulation.missing = true;
+                        result = Err(Error::UnknownModulator);
+                    }
+                }
+            }
         }
 
-        self.total_modulation = self.modulations.iter().map(|m| m.result).sum();
+        self.total_modulation = sum_results(&self.modulations, ModulationUnit::Range);
+        self.total_octaves = sum_results(&self.modulations, ModulationUnit::Octaves);
         self.update_final_value();
+        result
     }
 
     fn update_final_value(&mut self) {
-        let raw_final_value = self.value + self.total_modulation;
-        self.final_value = raw_final_value.clamp(self.minimum_value, self.maximum_value);
+        self.final_value = self.modulated(self.value);
+    }
+
+    fn modulated(&self, value: f32) -> f32 {
+        self.clamp((value + self.total_modulation) * self.total_octaves.exp2())
+    }
+
+    fn clamp(&self, value: f32) -> f32 {
+        value.clamp(self.minimum_value, self.maximum_value)
+    }
+}
+
+fn check_range(value: f32, minimum_value: f32, maximum_value: f32) -> Result<()> {
+    if minimum_value <= value && value <= maximum_value {
+        Ok(())
+    } else {
+        Err(Error::ParameterOutOfRange {
+            value,
+            minimum_value,
+            maximum_value,
+        })
+    }
+}
+
+fn sum_results(modulations: &[Modulation], unit: ModulationUnit) -> f32 {
+    modulations
+        .iter()
+        .filter(|m| m.mapping.unit == unit)
+        .map(|m| m.result)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::concepts::ModulationSampleIndex;
+    use crate::testing::AlternatingModulator;
+
+    fn ramped_parameter(smoothing: Smoothing) -> (Parameter, ModulationComponentsStore) {
+        let mut modulators = ModulationComponentsStore::default();
+        let modulator_id = modulators.add_component(Box::new(AlternatingModulator::new(1.0)));
+
+        let mut parameter = Parameter::new(100.0, 0.0, 1000.0);
+        parameter.set_smoothing(smoothing);
+        parameter.add_modulation(modulator_id, 0.3);
+        parameter
+            .apply_modulations(&modulators, AudioSampleIndex(1000))
+            .unwrap();
+
+        (parameter, modulators)
+    }
+
+    #[test]
+    fn keeps_modulations_of_missing_modulators() {
+        let mut modulators = ModulationComponentsStore::default();
+        let modulator_id = modulators.add_component(Box::new(AlternatingModulator::new(1.0)));
+
+        let mut parameter = Parameter::new(0.25, 0.0, 1.0);
+        parameter
+            .add_mapped_modulation(
+                modulator_id,
+                0.5,
+                ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Linear),
+            )
+            .unwrap();
+        parameter
+            .apply_modulations(&modulators, AudioSampleIndex(0))
+            .unwrap();
+        assert_eq!(parameter.final_value(), 0.75);
+
+        let modulator = modulators.take_component(modulator_id).unwrap();
+        assert_eq!(
+            parameter.apply_modulations(&modulators, AudioSampleIndex(100)),
+            Err(Error::UnknownModulator)
+        );
+        assert_eq!(parameter.final_value(), 0.25);
+        assert_eq!(
+            parameter.apply_modulations(&modulators, AudioSampleIndex(200)),
+            Ok(())
+        );
+        assert_eq!(parameter.iter_modulators().count(), 1);
+
+        modulators.restore_component(modulator_id, modulator);
+        parameter
+            .apply_modulations(&modulators, AudioSampleIndex(300))
+            .unwrap();
+        assert_eq!(parameter.final_value(), 0.75);
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let out_of_range = Error::ParameterOutOfRange {
+            value: 2.0,
+            minimum_value: -1.0,
+            maximum_value: 1.0,
+        };
+        assert_eq!(Parameter::try_new(2.0, -1.0, 1.0).err(), Some(out_of_range));
+
+        let mut parameter = Parameter::try_new(0.5, -1.0, 1.0).unwrap();
+        assert!(parameter.try_set_value(f32::NAN).is_err());
+        assert_eq!(parameter.try_set_value(-1.0), Ok(()));
+        assert_eq!(parameter.get_value(), -1.0);
+        assert_eq!(parameter.final_value(), -1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_on_out_of_range_values() {
+        Parameter::new(0.0, -1.0, 1.0).set_value(1.5);
+    }
+
+    #[test]
+    fn ramps_linearly_between_modulations() {
+        let (parameter, _) = ramped_parameter(Smoothing::Linear(AudioSampleDifference(100)));
+
+        assert_eq!(parameter.final_value(), 400.0);
+        assert_eq!(parameter.value_at(AudioSampleIndex(1000)), 100.0);
+        assert_eq!(parameter.value_at(AudioSampleIndex(1050)), 250.0);
+        assert_eq!(parameter.value_at(AudioSampleIndex(1100)), 400.0);
+        assert_eq!(parameter.value_at(AudioSampleIndex(5000)), 400.0);
+    }
+
+    #[test]
+    fn ramps_exponentially_between_modulations() {
+        let (parameter, _) = ramped_parameter(Smoothing::Exponential(AudioSampleDifference(100)));
+
+        assert_eq!(parameter.value_at(AudioSampleIndex(1000)), 100.0);
+        assert!((parameter.value_at(AudioSampleIndex(1050)) - 200.0).abs() < 1e-3);
+        assert_eq!(parameter.value_at(AudioSampleIndex(1100)), 400.0);
+    }
+
+    #[test]
+    fn restarts_ramp_from_current_value() {
+        let (mut parameter, mut modulators) =
+            ramped_parameter(Smoothing::Linear(AudioSampleDifference(100)));
+
+        for m in modulators.iter_components_mut() {
+            m.process_modulation(ModulationSampleIndex(0));
+        }
+        parameter
+            .apply_modulations(&modulators, AudioSampleIndex(1050))
+            .unwrap();
+
+        assert_eq!(parameter.final_value(), 100.0);
+        assert_eq!(parameter.value_at(AudioSampleIndex(1050)), 250.0);
+        assert_eq!(parameter.value_at(AudioSampleIndex(1100)), 175.0);
+    }
+
+    #[test]
+    fn ignores_smoothing_when_disabled() {
+        let (parameter, _) = ramped_parameter(Smoothing::None);
+
+        assert_eq!(parameter.value_at(AudioSampleIndex(1000)), 400.0);
+        assert_eq!(parameter.value_at(AudioSampleIndex(1050)), 400.0);
+    }
+
+    fn modulated_value(level: f32, mapping: ModulationMapping, modulator_level: f32) -> f32 {
+        let mut modulators = ModulationComponentsStore::default();
+        let modulator_id =
+            modulators.add_component(Box::new(AlternatingModulator::new(modulator_level)));
+
+        let mut parameter = Parameter::new(400.0, 0.0, 1000.0);
+        parameter
+            .add_mapped_modulation(modulator_id, level, mapping)
+            .unwrap();
+        parameter
+            .apply_modulations(&modulators, AudioSampleIndex(0))
+            .unwrap();
+        parameter.final_value()
+    }
+
+    #[test]
+    fn maps_bipolar_modulation_around_value() {
+        let mapping = ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Linear);
+
+        assert_eq!(modulated_value(0.1, mapping, -1.0), 300.0);
+        assert_eq!(modulated_value(0.1, mapping, 0.0), 400.0);
+        assert_eq!(modulated_value(0.1, mapping, 1.0), 500.0);
+    }
+
+    #[test]
+    fn maps_unipolar_modulation_above_value() {
+        let mapping = ModulationMapping::range(Polarity::Unipolar, ModulationCurve::Linear);
+
+        assert_eq!(modulated_value(0.1, mapping, -1.0), 400.0);
+        assert_eq!(modulated_value(0.1, mapping, 0.0), 450.0);
+        assert_eq!(modulated_value(0.1, mapping, 1.0), 500.0);
+    }
+
+    #[test]
+    fn applies_modulation_offset() {
+        let mapping =
+            ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Linear).with_offset(-50.0);
+
+        assert_eq!(modulated_value(0.1, mapping, 1.0), 450.0);
+    }
+
+    #[test]
+    fn shapes_modulation_with_curves() {
+        let exponential = ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Exponential);
+        let logarithmic = ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Logarithmic);
+
+        assert_eq!(modulated_value(0.1, exponential, 1.0), 500.0);
+        assert_eq!(modulated_value(0.1, logarithmic, -1.0), 300.0);
+
+        let exponential_half = modulated_value(0.1, exponential, 0.5);
+        let logarithmic_half = modulated_value(0.1, logarithmic, 0.5);
+        assert!(400.0 < exponential_half && exponential_half < 450.0);
+        assert!(450.0 < logarithmic_half && logarithmic_half < 500.0);
+        assert!(
+            (modulated_value(0.1, exponential, -0.5) - (800.0 - exponential_half)).abs() < 1e-3
+        );
+    }
+
+    #[test]
+    fn maps_modulation_in_octaves() {
+        let mapping = ModulationMapping::octaves(Polarity::Bipolar, ModulationCurve::Linear);
+
+        assert_eq!(modulated_value(1.0, mapping, 1.0), 800.0);
+        assert_eq!(modulated_value(1.0, mapping, -1.0), 200.0);
+        assert_eq!(modulated_value(2.0, mapping, -1.0), 100.0);
+        assert_eq!(modulated_value(2.0, mapping, 1.0), 1000.0);
     }
 }
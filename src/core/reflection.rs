@@ -0,0 +1,86 @@
+use crate::core::parameter::Parameter;
+use crate::core::topology::{AudioComponentId, ModulationComponentId};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ParameterUnit {
+    None,
+    Hertz,
+    Seconds,
+    Decibels,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ParameterDescriptor {
+    pub name: &'static str,
+    pub unit: ParameterUnit,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ParameterInfo {
+    pub name: &'static str,
+    pub unit: ParameterUnit,
+    pub minimum_value: f32,
+    pub maximum_value: f32,
+    pub default_value: f32,
+}
+
+// Parameters are addressed by their position in `parameter_descriptors`, which has to match
+// the indices accepted by `parameter` and `parameter_mut`.
+pub trait Parameterized {
+    // Identifies the component in patch files. Components without one can't be saved.
+    fn type_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn parameter_descriptors(&self) -> &'static [ParameterDescriptor] {
+        &[]
+    }
+    fn parameter(&self, _index: usize) -> Option<&Parameter> {
+        None
+    }
+    fn parameter_mut(&mut self, _index: usize) -> Option<&mut Parameter> {
+        None
+    }
+
+    fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.parameter_descriptors()
+            .iter()
+            .position(|d| d.name == name)
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParameterInfo> {
+        let descriptor = self.parameter_descriptors().get(index)?;
+        let parameter = self.parameter(index)?;
+        Some(ParameterInfo {
+            name: descriptor.name,
+            unit: descriptor.unit,
+            minimum_value: parameter.minimum_value(),
+            maximum_value: parameter.maximum_value(),
+            default_value: parameter.default_value(),
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ParameterOwner {
+    Audio(AudioComponentId),
+    Modulation(ModulationComponentId),
+}
+
+impl From<AudioComponentId> for ParameterOwner {
+    fn from(id: AudioComponentId) -> Self {
+        ParameterOwner::Audio(id)
+    }
+}
+
+impl From<ModulationComponentId> for ParameterOwner {
+    fn from(id: ModulationComponentId) -> Self {
+        ParameterOwner::Modulation(id)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ParameterId {
+    pub owner: ParameterOwner,
+    pub index: usize,
+}
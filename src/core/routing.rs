@@ -0,0 +1,285 @@
+use crate::core::buffers::{MultiChannelBuffer, MultiChannelSlice};
+use crate::core::concepts::{AudioSampleIndex, Channels};
+use crate::core::mixer::mix_channels;
+use crate::core::topology::{AudioComponentId, AudioComponentsStore};
+use crate::core::traits::AudioInputs;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AudioInputIndex(pub usize);
+
+#[derive(PartialEq, Debug)]
+pub enum RoutingError {
+    UnknownComponent,
+    UnknownInput,
+    Cycle,
+}
+
+impl fmt::Display for RoutingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoutingError::UnknownComponent => write!(f, "unknown audio component"),
+            RoutingError::UnknownInput => write!(f, "unknown audio component input"),
+            RoutingError::Cycle => write!(f, "audio connection would create a cycle"),
+        }
+    }
+}
+
+impl std::error::Error for RoutingError {}
+
+#[derive(Copy, Clone)]
+pub struct Connection {
+    pub source: AudioComponentId,
+    pub destination: AudioComponentId,
+    pub input: AudioInputIndex,
+    pub gain: f32,
+}
+
+struct RoutingNode {
+    component: AudioComponentId,
+    inputs: Vec<MultiChannelBuffer>,
+    output: MultiChannelBuffer,
+    bypassed: bool,
+}
+
+pub struct AudioRouting {
+    nodes: Vec<RoutingNode>,
+    connections: Vec<Connection>,
+    processing_order: Vec<usize>,
+    samples_per_step: usize,
+}
+
+impl AudioRouting {
+    pub fn new(samples_per_step: usize) -> Self {
+        Self {
+            nodes: vec![],
+            connections: vec![],
+            processing_order: vec![],
+            samples_per_step,
+        }
+    }
+
+    pub fn add_node(
+        &mut self,
+        component: AudioComponentId,
+        number_of_inputs: usize,
+        channels: Channels,
+    ) {
+        let samples_per_step = self.samples_per_step;
+        self.nodes.push(RoutingNode {
+            component,
+            inputs: (0..number_of_inputs)
+                .map(|_| MultiChannelBuffer::new(channels, samples_per_step))
+                .collect(),
+            output: MultiChannelBuffer::new(channels, samples_per_step),
+            bypassed: false,
+        });
+
+        self.processing_order = self
+            .compute_processing_order()
+            .expect("a node without connections can't create a cycle");
+    }
+
+    pub fn remove_node(&mut self, component: AudioComponentId) {
+        self.connections
+            .retain(|c| c.source != component && c.destination != component);
+        self.nodes.retain(|n| n.component != component);
+
+        self.processing_order = self
+            .compute_processing_order()
+            .expect("removing a node can't create a cycle");
+    }
+
+    pub fn set_bypass(
+        &mut self,
+        component: AudioComponentId,
+        bypassed: bool,
+    ) -> Result<(), RoutingError> {
+        let node = self
+            .find_node(component)
+            .ok_or(RoutingError::UnknownComponent)?;
+        self.nodes[node].bypassed = bypassed;
+        Ok(())
+    }
+
+    pub fn is_bypassed(&self, component: AudioComponentId) -> Option<bool> {
+        self.find_node(component).map(|n| self.nodes[n].bypassed)
+    }
+
+    pub fn connect(
+        &mut self,
+        source: AudioComponentId,
+        destination: AudioComponentId,
+        input: AudioInputIndex,
+        gain: f32,
+    ) -> Result<(), RoutingError> {
+        if self.find_node(source).is_none() {
+            return Err(RoutingError::UnknownComponent);
+        }
+        let destination_node = self
+            .find_node(destination)
+            .ok_or(RoutingError::UnknownComponent)?;
+        if input.0 >= self.nodes[destination_node].inputs.len() {
+            return Err(RoutingError::UnknownInput);
+        }
+
+        self.connections.push(Connection {
+            source,
+            destination,
+            input,
+            gain,
+        });
+
+        match self.compute_processing_order() {
+            Some(order) => {
+                self.processing_order = order;
+                Ok(())
+            }
+            None => {
+                self.connections.pop();
+                Err(RoutingError::Cycle)
+            }
+        }
+    }
+
+    pub fn disconnect(
+        &mut self,
+        source: AudioComponentId,
+        destination: AudioComponentId,
+        input: AudioInputIndex,
+    ) {
+        self.connections
+            .retain(|c| !(c.source == source && c.destination == destination && c.input == input));
+    }
+
+    pub fn iter_connections(&self) -> impl Iterator<Item = &Connection> {
+        self.connections.iter()
+    }
+
+    pub fn processing_order(&self) -> impl Iterator<Item = AudioComponentId> + '_ {
+        self.processing_order
+            .iter()
+            .map(move |n| self.nodes[*n].component)
+    }
+
+    pub fn get_output(
+        &self,
+        component: AudioComponentId,
+        samples: usize,
+    ) -> Option<MultiChannelSlice<'_>> {
+        self.find_node(component)
+            .map(|n| self.nodes[n].output.slice(0..samples))
+    }
+
+    pub fn process(
+        &mut self,
+        components: &mut AudioComponentsStore,
+        sample_range: Range<AudioSampleIndex>,
+    ) {
+        let samples = (sample_range.end - sample_range.start).0 as usize;
+
+        for order_index in 0..self.processing_order.len() {
+            let node_index = self.processing_order[order_index];
+            let component_id = self.nodes[node_index].component;
+
+            for input_index in 0..self.nodes[node_index].inputs.len() {
+                let mut input = self.nodes[node_index].inputs[input_index].slice_mut(0..samples);
+                input.fill(0.0);
+            }
+
+            for connection in self
+                .connections
+                .iter()
+                .filter(|c| c.destination == component_id)
+            {
+                let source_index = self
+                    .nodes
+                    .iter()
+                    .position(|n| n.component == connection.source)
+                    .unwrap();
+                let (source, destination) =
+                    node_pair_mut(&mut self.nodes, source_index, node_index);
+                mix_channels(
+                    &mut destination.inputs[connection.input.0].slice_mut(0..samples),
+                    &source.output.slice(0..samples),
+                    connection.gain,
+                    0.0,
+                );
+            }
+
+            let node = &mut self.nodes[node_index];
+            // Bypassed effects pass their first input through, bypassed generators are silent.
+            if node.bypassed {
+                let mut output = node.output.slice_mut(0..samples);
+                output.fill(0.0);
+                if let Some(input) = node.inputs.first() {
+                    mix_channels(&mut output, &input.slice(0..samples), 1.0, 0.0);
+                }
+                continue;
+            }
+
+            if let Some(c) = components.get_component_mut(component_id) {
+                let inputs = AudioInputs::new(&node.inputs, samples);
+                c.process_audio(
+                    &inputs,
+                    &mut node.output.slice_mut(0..samples),
+                    sample_range.clone(),
+                );
+            }
+        }
+    }
+
+    fn find_node(&self, component: AudioComponentId) -> Option<usize> {
+        self.nodes.iter().position(|n| n.component == component)
+    }
+
+    fn compute_processing_order(&self) -> Option<Vec<usize>> {
+        let mut pending_inputs: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|n| {
+                self.connections
+                    .iter()
+                    .filter(|c| c.destination == n.component)
+                    .count()
+            })
+            .collect();
+        let mut processed = vec![false; self.nodes.len()];
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        while order.len() < self.nodes.len() {
+            let ready =
+                (0..self.nodes.len()).find(|n| !processed[*n] && pending_inputs[*n] == 0)?;
+
+            processed[ready] = true;
+            order.push(ready);
+
+            for connection in self
+                .connections
+                .iter()
+                .filter(|c| c.source == self.nodes[ready].component)
+            {
+                let destination = self.find_node(connection.destination).unwrap();
+                pending_inputs[destination] -= 1;
+            }
+        }
+
+        Some(order)
+    }
+}
+
+fn node_pair_mut(
+    nodes: &mut [RoutingNode],
+    source: usize,
+    destination: usize,
+) -> (&RoutingNode, &mut RoutingNode) {
+    assert_ne!(source, destination);
+    if source < destination {
+        let (head, tail) = nodes.split_at_mut(destination);
+        (&head[source], &mut tail[0])
+    } else {
+        let (head, tail) = nodes.split_at_mut(source);
+        (&tail[0], &mut head[destination])
+    }
+}
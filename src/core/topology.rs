@@ -1,17 +1,26 @@
+use crate::core::buffers::MultiChannelBuffer;
+use crate::core::commands::Command;
 use crate::core::component_store::{ComponentId, ComponentsStore};
+use crate::core::concepts::AudioSampleIndex;
+use crate::core::mixer::{Mixer, MixerInput};
+use crate::core::modulation::{compute_modulation_order, ModulationError, ModulationOrderScratch};
+use crate::core::parameter::{ModulationMapping, Parameter, MODULATION_CAPACITY};
+use crate::core::reflection::{ParameterId, ParameterInfo, ParameterOwner, Parameterized};
+use crate::core::routing::{AudioInputIndex, AudioRouting, RoutingError};
 use crate::core::traits::{AudioComponent, ModulationComponent};
 use crate::core::EngineSpec;
+use crate::error::Error;
 
 pub type DynAudioComponent = Box<dyn AudioComponent>;
 pub type AudioComponents = Vec<DynAudioComponent>;
 pub type DynModulationComponent = Box<dyn ModulationComponent>;
 pub type ModulationComponents = Vec<DynModulationComponent>;
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub struct ModulationComponentIdTag;
 pub type ModulationComponentId = ComponentId<ModulationComponentIdTag>;
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub struct AudioComponentIdTag;
 pub type AudioComponentId = ComponentId<AudioComponentIdTag>;
 
@@ -21,35 +30,612 @@ pub type ModulationComponentsStore =
 
 pub struct AudioTopology {
     pub spec: EngineSpec,
-    pub processing_buffer: Vec<f32>,
+    pub processing_buffer: MultiChannelBuffer,
     pub audio_components: AudioComponentsStore,
+    pub routing: AudioRouting,
+    pub mixer: Mixer,
     pub modulation_components: ModulationComponentsStore,
+    pub modulation_order: Vec<ModulationComponentId>,
+    next_modulation_order: Vec<ModulationComponentId>,
+    modulation_scratch: ModulationOrderScratch,
+    names: Vec<(String, ParameterOwner)>,
 }
 
 impl AudioTopology {
     pub fn new(spec: EngineSpec) -> Self {
         Self {
             spec,
-            processing_buffer: vec![0.0; spec.max_samples_per_step],
+            processing_buffer: MultiChannelBuffer::new(spec.channels, spec.max_samples_per_step),
             audio_components: ComponentsStore::default(),
+            routing: AudioRouting::new(spec.max_samples_per_step),
+            mixer: Mixer::default(),
             modulation_components: ComponentsStore::default(),
+            modulation_order: vec![],
+            next_modulation_order: vec![],
+            modulation_scratch: ModulationOrderScratch::default(),
+            names: vec![],
         }
     }
 
     pub fn add_component<T: 'static + AudioComponent>(&mut self, component: T) -> AudioComponentId {
-        let boxed = Box::new(component);
-        self.audio_components.add_component(boxed)
+        self.add_boxed_component(Box::new(component))
+    }
+
+    pub fn add_boxed_component(&mut self, component: DynAudioComponent) -> AudioComponentId {
+        let number_of_inputs = component.number_of_inputs();
+        let channels = component.channels();
+        let id = self.audio_components.add_component(component);
+        self.routing.add_node(id, number_of_inputs, channels);
+        self.mixer.add_input(id, 1.0);
+        id
+    }
+
+    pub fn remove_component(&mut self, id: AudioComponentId) -> Option<DynAudioComponent> {
+        let removed = self.audio_components.remove_component(id)?;
+        self.routing.remove_node(id);
+        self.mixer.remove_input(id);
+        self.remove_name(id.into());
+        Some(removed)
+    }
+
+    pub fn connect(
+        &mut self,
+        source: AudioComponentId,
+        destination: AudioComponentId,
+        input: AudioInputIndex,
+        gain: f32,
+    ) -> Result<(), RoutingError> {
+        self.routing.connect(source, destination, input, gain)
+    }
+
+    pub fn disconnect(
+        &mut self,
+        source: AudioComponentId,
+        destination: AudioComponentId,
+        input: AudioInputIndex,
+    ) {
+        self.routing.disconnect(source, destination, input)
+    }
+
+    pub fn connect_to_output(&mut self, id: AudioComponentId, gain: f32) {
+        match self.mixer.get_input_mut(id) {
+            Some(input) => input.gain = gain,
+            None => self.mixer.add_input(id, gain),
+        }
+    }
+
+    pub fn disconnect_from_output(&mut self, id: AudioComponentId) {
+        self.mixer.remove_input(id);
+    }
+
+    pub fn get_mixer_input_mut(&mut self, id: AudioComponentId) -> Option<&mut MixerInput> {
+        self.mixer.get_input_mut(id)
     }
 
     pub fn add_modulator<T: 'static + ModulationComponent>(
         &mut self,
         modulator: T,
-    ) -> ModulationComponentId {
-        let boxed = Box::new(modulator);
-        self.modulation_components.add_component(boxed)
+    ) -> Result<ModulationComponentId, ModulationError> {
+        self.add_boxed_modulator(Box::new(modulator))
+    }
+
+    pub fn add_boxed_modulator(
+        &mut self,
+        modulator: DynModulationComponent,
+    ) -> Result<ModulationComponentId, ModulationError> {
+        let id = self.modulation_components.add_component(modulator);
+        let modulators = self.modulation_components.len();
+        let modulations: usize = self
+            .modulation_components
+            .iter_components()
+            .map(|m| m.parameter_descriptors().len() * MODULATION_CAPACITY)
+            .sum();
+        self.modulation_order.reserve(modulators);
+        self.next_modulation_order.reserve(modulators);
+        self.modulation_scratch
+            .reserve(self.modulation_components.slot_count(), modulations);
+
+        if let Err(e) = self.update_modulation_order() {
+            self.modulation_components.remove_component(id);
+            return Err(e);
+        }
+
+        Ok(id)
+    }
+
+    pub fn remove_modulator(
+        &mut self,
+        id: ModulationComponentId,
+    ) -> Option<DynModulationComponent> {
+        let removed = self.modulation_components.remove_component(id)?;
+        self.update_modulation_order()
+            .expect("removing a modulator can't create a cycle");
+        self.remove_name(id.into());
+        self.remove_modulations_from(id);
+        Some(removed)
+    }
+
+    // Parameters keep modulations of missing modulators, so they're cleaned up here.
+    fn remove_modulations_from(&mut self, modulator: ModulationComponentId) {
+        for component in self.audio_components.iter_components_mut() {
+            remove_modulations(component, modulator);
+        }
+        for component in self.modulation_components.iter_components_mut() {
+            remove_modulations(component, modulator);
+        }
     }
 
     pub fn get_modulator(&self, id: ModulationComponentId) -> Option<&dyn ModulationComponent> {
         self.modulation_components.get_component(id)
     }
+
+    // Modulations added to a modulator through here skip cycle detection and leave the modulation
+    // order stale, `add_modulation` takes care of both.
+    pub fn get_modulator_mut(
+        &mut self,
+        id: ModulationComponentId,
+    ) -> Option<&mut (dyn ModulationComponent + 'static)> {
+        self.modulation_components.get_component_mut(id)
+    }
+
+    // Modulations of modulator parameters reorder the modulators, and are rejected when they'd
+    // form a cycle. Nothing is allocated, so parameters only take as many modulations as they
+    // have room for.
+    pub fn add_modulation(
+        &mut self,
+        parameter: ParameterId,
+        modulator: ModulationComponentId,
+        level: f32,
+        mapping: ModulationMapping,
+    ) -> crate::error::Result<()> {
+        if !self.modulation_components.contains(modulator) {
+            return Err(Error::UnknownModulator);
+        }
+        self.get_parameter_mut(parameter)
+            .ok_or(Error::UnknownParameter)?
+            .add_mapped_modulation(modulator, level, mapping)?;
+
+        let result = self.update_modulation_order_for(parameter);
+        if result.is_err() {
+            self.get_parameter_mut(parameter)
+                .expect("the parameter was just modulated")
+                .remove_modulation(modulator);
+        }
+        result
+    }
+
+    pub fn remove_modulation(
+        &mut self,
+        parameter: ParameterId,
+        modulator: ModulationComponentId,
+    ) -> crate::error::Result<()> {
+        self.get_parameter_mut(parameter)
+            .ok_or(Error::UnknownParameter)?
+            .remove_modulation(modulator);
+        self.update_modulation_order_for(parameter)
+    }
+
+    // The order is computed next to the current one, which stays in place if there's a cycle.
+    pub fn update_modulation_order(&mut self) -> Result<(), ModulationError> {
+        compute_modulation_order(
+            &self.modulation_components,
+            &mut self.modulation_scratch,
+            &mut self.next_modulation_order,
+        )?;
+        std::mem::swap(&mut self.modulation_order, &mut self.next_modulation_order);
+        Ok(())
+    }
+
+    // Names make parameters addressable by path, e.g. "osc1.frequency".
+    pub fn set_name<T: Into<ParameterOwner>>(&mut self, owner: T, name: &str) {
+        let owner = owner.into();
+        self.names.retain(|(n, o)| n != name && *o != owner);
+        self.names.push((name.to_string(), owner));
+    }
+
+    pub fn get_name<T: Into<ParameterOwner>>(&self, owner: T) -> Option<&str> {
+        let owner = owner.into();
+        self.names
+            .iter()
+            .find(|(_, o)| *o == owner)
+            .map(|(n, _)| n.as_str())
+    }
+
+    pub fn find_owner(&self, name: &str) -> Option<ParameterOwner> {
+        self.names.iter().find(|(n, _)| n == name).map(|(_, o)| *o)
+    }
+
+    pub fn find_parameter(&self, path: &str) -> Option<ParameterId> {
+        let (name, parameter) = path.split_once('.')?;
+        let owner = self.find_owner(name)?;
+        let index = match owner {
+            ParameterOwner::Audio(id) => self
+                .audio_components
+                .get_component(id)?
+                .parameter_index(parameter)?,
+            ParameterOwner::Modulation(id) => self
+                .modulation_components
+                .get_component(id)?
+                .parameter_index(parameter)?,
+        };
+        Some(ParameterId { owner, index })
+    }
+
+    pub fn parameter_paths(&self) -> Vec<(String, ParameterId)> {
+        let mut paths = vec![];
+        for (name, owner) in self.names.iter() {
+            let descriptors = match *owner {
+                ParameterOwner::Audio(id) => self
+                    .audio_components
+                    .get_component(id)
+                    .map(|c| c.parameter_descriptors()),
+                ParameterOwner::Modulation(id) => self
+                    .modulation_components
+                    .get_component(id)
+                    .map(|c| c.parameter_descriptors()),
+            };
+            for (index, descriptor) in descriptors.unwrap_or(&[]).iter().enumerate() {
+                paths.push((
+                    format!("{}.{}", name, descriptor.name),
+                    ParameterId {
+                        owner: *owner,
+                        index,
+                    },
+                ));
+            }
+        }
+        paths
+    }
+
+    pub fn get_parameter(&self, id: ParameterId) -> Option<&Parameter> {
+        match id.owner {
+            ParameterOwner::Audio(owner) => self
+                .audio_components
+                .get_component(owner)?
+                .parameter(id.index),
+            ParameterOwner::Modulation(owner) => self
+                .modulation_components
+                .get_component(owner)?
+                .parameter(id.index),
+        }
+    }
+
+    // The same caveat as `get_modulator_mut` applies to modulator parameters.
+    pub fn get_parameter_mut(&mut self, id: ParameterId) -> Option<&mut Parameter> {
+        match id.owner {
+            ParameterOwner::Audio(owner) => self
+                .audio_components
+                .get_component_mut(owner)?
+                .parameter_mut(id.index),
+            ParameterOwner::Modulation(owner) => self
+                .modulation_components
+                .get_component_mut(owner)?
+                .parameter_mut(id.index),
+        }
+    }
+
+    pub fn parameter_info(&self, id: ParameterId) -> Option<ParameterInfo> {
+        match id.owner {
+            ParameterOwner::Audio(owner) => self
+                .audio_components
+                .get_component(owner)?
+                .parameter_info(id.index),
+            ParameterOwner::Modulation(owner) => self
+                .modulation_components
+                .get_component(owner)?
+                .parameter_info(id.index),
+        }
+    }
+
+    pub fn set_bypass(&mut self, id: AudioComponentId, bypassed: bool) -> Result<(), RoutingError> {
+        self.routing.set_bypass(id, bypassed)
+    }
+
+    pub fn is_bypassed(&self, id: AudioComponentId) -> Option<bool> {
+        self.routing.is_bypassed(id)
+    }
+
+    pub fn apply_command(
+        &mut self,
+        command: Command,
+        sample: AudioSampleIndex,
+    ) -> crate::error::Result<()> {
+        match command {
+            Command::SetParameter { parameter, value } => self
+                .get_parameter_mut(parameter)
+                .ok_or(Error::UnknownParameter)?
+                .try_set_value(value),
+            Command::AddModulation {
+                parameter,
+                modulator,
+                level,
+                mapping,
+            } => self.add_modulation(parameter, modulator, level, mapping),
+            Command::RemoveModulation {
+                parameter,
+                modulator,
+            } => self.remove_modulation(parameter, modulator),
+            Command::NoteOn {
+                component,
+                note,
+                velocity,
+            } => {
+                self.audio_components
+                    .get_component_mut(component)
+                    .ok_or(Error::UnknownComponent)?
+                    .note_on(note, velocity, sample);
+                Ok(())
+            }
+            Command::NoteOff { component, note } => {
+                self.audio_components
+                    .get_component_mut(component)
+                    .ok_or(Error::UnknownComponent)?
+                    .note_off(note, sample);
+                Ok(())
+            }
+            Command::ControlChange {
+                component,
+                controller,
+                value,
+            } => {
+                self.audio_components
+                    .get_component_mut(component)
+                    .ok_or(Error::UnknownComponent)?
+                    .control_change(controller, value, sample);
+                Ok(())
+            }
+            // Reported like the other commands addressing a missing component.
+            Command::SetBypass {
+                component,
+                bypassed,
+            } => self
+                .set_bypass(component, bypassed)
+                .map_err(|_| Error::UnknownComponent),
+        }
+    }
+
+    // Only modulator parameters take part in the modulation order.
+    fn update_modulation_order_for(&mut self, parameter: ParameterId) -> crate::error::Result<()> {
+        match parameter.owner {
+            ParameterOwner::Modulation(_) => Ok(self.update_modulation_order()?),
+            ParameterOwner::Audio(_) => Ok(()),
+        }
+    }
+
+    fn remove_name(&mut self, owner: ParameterOwner) {
+        self.names.retain(|(_, o)| *o != owner);
+    }
+}
+
+fn remove_modulations<T: Parameterized + ?Sized>(
+    component: &mut T,
+    modulator: ModulationComponentId,
+) {
+    for index in 0..component.parameter_descriptors().len() {
+        if let Some(parameter) = component.parameter_mut(index) {
+            parameter.remove_modulation(modulator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::commands::Command;
+    use crate::core::concepts::{Channels, ModulationRate, SamplingRate};
+    use crate::core::empty_engine;
+    use crate::core::modulation::ModulationError;
+    use crate::core::parameter::{
+        ModulationCurve, ModulationMapping, Polarity, MODULATION_CAPACITY,
+    };
+    use crate::core::reflection::ParameterUnit;
+    use crate::testing::{ConstantGenerator, FollowingModulator};
+
+    fn empty_topology() -> AudioTopology {
+        let (_, topology) =
+            empty_engine(SamplingRate(48000), ModulationRate(100), 128, Channels(1));
+        topology
+    }
+
+    #[test]
+    fn addresses_parameters_by_path() {
+        let mut topology = empty_topology();
+        let generator_id = topology.add_component(ConstantGenerator::default());
+        let modulator_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        topology.set_name(generator_id, "gen1");
+        topology.set_name(modulator_id, "mod1");
+
+        let level = topology.find_parameter("gen1.level").unwrap();
+        assert_eq!(level.owner, ParameterOwner::Audio(generator_id));
+        assert_eq!(topology.find_parameter("gen1.frequency"), None);
+        assert_eq!(topology.find_parameter("gen2.level"), None);
+        assert_eq!(topology.find_parameter("gen1"), None);
+
+        topology.get_parameter_mut(level).unwrap().set_value(0.5);
+        assert_eq!(topology.get_parameter(level).unwrap().get_value(), 0.5);
+
+        let info = topology.parameter_info(level).unwrap();
+        assert_eq!(info.name, "level");
+        assert_eq!(info.unit, ParameterUnit::None);
+        assert_eq!(
+            (info.minimum_value, info.maximum_value, info.default_value),
+            (-1.0, 1.0, 1.0)
+        );
+
+        let paths: Vec<String> = topology
+            .parameter_paths()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(paths, vec!["gen1.level", "mod1.level"]);
+    }
+
+    #[test]
+    fn forgets_names_of_removed_components() {
+        let mut topology = empty_topology();
+        let generator_id = topology.add_component(ConstantGenerator::default());
+        topology.set_name(generator_id, "gen1");
+        let level = topology.find_parameter("gen1.level").unwrap();
+
+        topology.remove_component(generator_id);
+
+        assert_eq!(topology.get_name(generator_id), None);
+        assert_eq!(topology.find_parameter("gen1.level"), None);
+        assert!(topology.get_parameter(level).is_none());
+    }
+
+    #[test]
+    fn rejects_modulation_commands_creating_cycles() {
+        let mut topology = empty_topology();
+        let modulator1_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        let modulator2_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        topology.set_name(modulator1_id, "mod1");
+        topology.set_name(modulator2_id, "mod2");
+        let add_modulation = |parameter, modulator| Command::AddModulation {
+            parameter,
+            modulator,
+            level: 0.5,
+            mapping: ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Linear),
+        };
+
+        let level1 = topology.find_parameter("mod1.level").unwrap();
+        let level2 = topology.find_parameter("mod2.level").unwrap();
+        topology
+            .apply_command(add_modulation(level2, modulator1_id), AudioSampleIndex(0))
+            .unwrap();
+        assert_eq!(
+            topology.modulation_order,
+            vec![modulator1_id, modulator2_id]
+        );
+
+        assert_eq!(
+            topology.apply_command(add_modulation(level1, modulator2_id), AudioSampleIndex(0)),
+            Err(Error::Modulation(ModulationError::Cycle))
+        );
+        assert_eq!(
+            topology
+                .get_parameter(level1)
+                .unwrap()
+                .iter_modulators()
+                .count(),
+            0
+        );
+        assert_eq!(
+            topology.modulation_order,
+            vec![modulator1_id, modulator2_id]
+        );
+    }
+
+    #[test]
+    fn edits_modulations_in_reserved_memory() {
+        let mut topology = empty_topology();
+        let modulator1_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        let modulator2_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        topology.set_name(modulator2_id, "mod2");
+        let level = topology.find_parameter("mod2.level").unwrap();
+        let mapping = ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Linear);
+        let buffers = [
+            topology.modulation_order.as_ptr(),
+            topology.next_modulation_order.as_ptr(),
+        ];
+
+        for _ in 0..MODULATION_CAPACITY {
+            topology
+                .add_modulation(level, modulator1_id, 0.1, mapping)
+                .unwrap();
+        }
+        assert_eq!(
+            topology.add_modulation(level, modulator1_id, 0.1, mapping),
+            Err(Error::TooManyModulations)
+        );
+        topology.remove_modulation(level, modulator1_id).unwrap();
+
+        assert!(buffers.contains(&topology.modulation_order.as_ptr()));
+        assert!(buffers.contains(&topology.next_modulation_order.as_ptr()));
+        assert_eq!(
+            topology.modulation_order,
+            vec![modulator1_id, modulator2_id]
+        );
+    }
+
+    #[test]
+    fn orders_long_modulation_chains() {
+        let mut topology = empty_topology();
+        let ids: Vec<_> = (0..300)
+            .map(|_| {
+                topology
+                    .add_modulator(FollowingModulator::default())
+                    .unwrap()
+            })
+            .collect();
+        let mapping = ModulationMapping::range(Polarity::Bipolar, ModulationCurve::Linear);
+        let buffers = [
+            topology.modulation_order.as_ptr(),
+            topology.next_modulation_order.as_ptr(),
+        ];
+
+        // Every modulator follows the one added after it.
+        for pair in ids.windows(2) {
+            let level = ParameterId {
+                owner: ParameterOwner::Modulation(pair[0]),
+                index: 0,
+            };
+            topology
+                .add_modulation(level, pair[1], 0.5, mapping)
+                .unwrap();
+        }
+        let last_level = ParameterId {
+            owner: ParameterOwner::Modulation(ids[299]),
+            index: 0,
+        };
+        assert_eq!(
+            topology.add_modulation(last_level, ids[0], 0.5, mapping),
+            Err(Error::Modulation(ModulationError::Cycle))
+        );
+
+        let reversed: Vec<_> = ids.iter().rev().copied().collect();
+        assert_eq!(topology.modulation_order, reversed);
+        assert!(buffers.contains(&topology.modulation_order.as_ptr()));
+        assert!(buffers.contains(&topology.next_modulation_order.as_ptr()));
+    }
+
+    #[test]
+    fn removes_modulations_of_removed_modulators() {
+        let mut topology = empty_topology();
+        let modulator_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        let follower_id = topology
+            .add_modulator(FollowingModulator::default())
+            .unwrap();
+        let generator_id = topology.add_component(ConstantGenerator::default());
+        topology.set_name(generator_id, "gen1");
+        topology.set_name(follower_id, "mod2");
+
+        let level = topology.find_parameter("gen1.level").unwrap();
+        let follower_level = topology.find_parameter("mod2.level").unwrap();
+        for parameter in [level, follower_level] {
+            topology
+                .get_parameter_mut(parameter)
+                .unwrap()
+                .add_modulation(modulator_id, 0.5);
+        }
+
+        topology.remove_modulator(modulator_id);
+
+        for parameter in [level, follower_level] {
+            let parameter = topology.get_parameter(parameter).unwrap();
+            assert_eq!(parameter.iter_modulators().count(), 0);
+        }
+    }
 }
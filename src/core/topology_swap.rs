@@ -0,0 +1,88 @@
+use crate::core::concepts::AudioSampleDifference;
+use crate::core::engine::EngineSpec;
+use crate::core::topology::AudioTopology;
+use crate::error::{Error, Result};
+use rtrb::{Consumer, Producer, RingBuffer};
+
+pub struct TopologySwap {
+    pub topology: Box<AudioTopology>,
+    pub crossfade: AudioSampleDifference,
+}
+
+// Replaced topologies travel back to the sender, so they're never dropped on the audio thread.
+pub fn topology_swap_queue(
+    spec: EngineSpec,
+    capacity: usize,
+) -> (TopologySender, TopologyReceiver) {
+    let (swaps, pending_swaps) = RingBuffer::new(capacity);
+    // One slot for each pending swap, plus the topologies being replaced or faded out.
+    let (retired, pending_retired) = RingBuffer::new(capacity + 2);
+
+    (
+        TopologySender {
+            spec,
+            swaps,
+            retired: pending_retired,
+        },
+        TopologyReceiver {
+            swaps: pending_swaps,
+            retired,
+        },
+    )
+}
+
+pub struct TopologySender {
+    spec: EngineSpec,
+    swaps: Producer<TopologySwap>,
+    retired: Consumer<Box<AudioTopology>>,
+}
+
+impl TopologySender {
+    pub fn send(
+        &mut self,
+        topology: AudioTopology,
+        crossfade: AudioSampleDifference,
+    ) -> Result<()> {
+        if !self.spec.is_compatible(&topology.spec) {
+            return Err(Error::IncompatibleTopology);
+        }
+
+        self.swaps
+            .push(TopologySwap {
+                topology: Box::new(topology),
+                crossfade,
+            })
+            .map_err(|_| Error::TopologyQueueFull)
+    }
+
+    // Drops the topologies the audio thread is done with. Returns how many were dropped.
+    pub fn collect_retired(&mut self) -> usize {
+        let mut collected = 0;
+        while self.retired.pop().is_ok() {
+            collected += 1;
+        }
+        collected
+    }
+}
+
+pub struct TopologyReceiver {
+    swaps: Consumer<TopologySwap>,
+    retired: Producer<Box<AudioTopology>>,
+}
+
+impl TopologyReceiver {
+    // A swap is only handed out while there's room to retire both the current topology and the
+    // one it may still be fading out from.
+    pub fn pop(&mut self) -> Option<TopologySwap> {
+        if self.retired.slots() < 2 {
+            return None;
+        }
+        self.swaps.pop().ok()
+    }
+
+    pub fn retire(&mut self, topology: Box<AudioTopology>) {
+        if self.retired.push(topology).is_err() {
+            unreachable!("swaps are only accepted while there's room to retire topologies");
+        }
+    }
+}
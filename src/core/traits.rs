@@ -1,17 +1,81 @@
-use crate::core::concepts::{AudioSampleIndex, ModulationSampleIndex};
+use crate::core::buffers::{MultiChannelBuffer, MultiChannelSlice, MultiChannelSliceMut};
+use crate::core::concepts::{AudioSampleIndex, Channels, ModulationSampleIndex};
+use crate::core::reflection::Parameterized;
+use crate::core::routing::AudioInputIndex;
 use crate::core::ModulationComponentsStore;
+use crate::error::Result;
 use std::ops::Range;
 
-pub trait AudioComponent: Send {
-    fn process_audio(&mut self, data: &mut [f32], sample_range: Range<AudioSampleIndex>);
+pub struct AudioInputs<'a> {
+    buffers: &'a [MultiChannelBuffer],
+    samples: usize,
+}
+
+impl<'a> AudioInputs<'a> {
+    pub fn new(buffers: &'a [MultiChannelBuffer], samples: usize) -> Self {
+        Self { buffers, samples }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            buffers: &[],
+            samples: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    pub fn get(&self, index: AudioInputIndex) -> Option<MultiChannelSlice<'a>> {
+        self.buffers.get(index.0).map(|b| b.slice(0..self.samples))
+    }
+}
+
+pub trait AudioComponent: Parameterized + Send {
+    fn number_of_inputs(&self) -> usize {
+        0
+    }
+    fn channels(&self) -> Channels {
+        Channels(1)
+    }
+    fn process_audio(
+        &mut self,
+        inputs: &AudioInputs,
+        output: &mut MultiChannelSliceMut,
+        sample_range: Range<AudioSampleIndex>,
+    );
     fn apply_modulations(
         &mut self,
         modulators: &ModulationComponentsStore,
         sample: AudioSampleIndex,
-    );
+    ) -> Result<()>;
+    fn apply_automation(&mut self, _sample: AudioSampleIndex) {}
+    fn note_on(&mut self, _note: u8, _velocity: u8, _sample: AudioSampleIndex) {}
+    fn note_off(&mut self, _note: u8, _sample: AudioSampleIndex) {}
+    fn control_change(&mut self, _controller: u8, _value: u8, _sample: AudioSampleIndex) {}
+    // Components that keep sounding after a note off, like envelopes, hold on to their voice.
+    fn is_active(&self) -> bool {
+        false
+    }
+    fn next_automation_breakpoint(&self, _sample: AudioSampleIndex) -> Option<AudioSampleIndex> {
+        None
+    }
 }
 
-pub trait ModulationComponent: Send {
+pub trait ModulationComponent: Parameterized + Send {
     fn process_modulation(&mut self, sample: ModulationSampleIndex);
     fn get_current_level(&self) -> f32;
+    fn apply_modulations(
+        &mut self,
+        _modulators: &ModulationComponentsStore,
+        _sample: AudioSampleIndex,
+    ) -> Result<()> {
+        Ok(())
+    }
+    fn apply_automation(&mut self, _sample: AudioSampleIndex) {}
 }
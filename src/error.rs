@@ -0,0 +1,103 @@
+use crate::core::modulation::ModulationError;
+use crate::core::routing::RoutingError;
+use crate::patch::PatchError;
+use std::fmt;
+
+#[derive(PartialEq, Debug)]
+pub enum Error {
+    ParameterOutOfRange {
+        value: f32,
+        minimum_value: f32,
+        maximum_value: f32,
+    },
+    InvalidEngine {
+        field: &'static str,
+        value: u64,
+    },
+    UnknownComponent,
+    UnknownParameter,
+    UnknownModulator,
+    TooManyModulations,
+    CommandQueueFull,
+    TopologyQueueFull,
+    IncompatibleTopology,
+    Routing(RoutingError),
+    Modulation(ModulationError),
+    Patch(PatchError),
+    Device(String),
+    Io(String),
+    MidiFile(String),
+    Wav(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ParameterOutOfRange {
+                value,
+                minimum_value,
+                maximum_value,
+            } => write!(
+                f,
+                "parameter value {} is outside of [{}, {}]",
+                value, minimum_value, maximum_value
+            ),
+            Error::InvalidEngine { field, value } => {
+                write!(f, "invalid engine setting {} = {}", field, value)
+            }
+            Error::UnknownComponent => write!(f, "unknown component"),
+            Error::UnknownParameter => write!(f, "unknown parameter"),
+            Error::UnknownModulator => write!(f, "unknown modulator"),
+            Error::TooManyModulations => write!(f, "parameter has no room for more modulations"),
+            Error::CommandQueueFull => write!(f, "command queue is full"),
+            Error::TopologyQueueFull => write!(f, "topology swap queue is full"),
+            Error::IncompatibleTopology => {
+                write!(f, "topology was built for a different engine configuration")
+            }
+            Error::Routing(e) => write!(f, "{}", e),
+            Error::Modulation(e) => write!(f, "{}", e),
+            Error::Patch(e) => write!(f, "{}", e),
+            Error::Device(message) => write!(f, "audio device error: {}", message),
+            Error::Io(message) => write!(f, "i/o error: {}", message),
+            Error::MidiFile(message) => write!(f, "invalid MIDI file: {}", message),
+            Error::Wav(message) => write!(f, "WAV file error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Routing(e) => Some(e),
+            Error::Modulation(e) => Some(e),
+            Error::Patch(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<RoutingError> for Error {
+    fn from(e: RoutingError) -> Self {
+        Error::Routing(e)
+    }
+}
+
+impl From<ModulationError> for Error {
+    fn from(e: ModulationError) -> Self {
+        Error::Modulation(e)
+    }
+}
+
+impl From<PatchError> for Error {
+    fn from(e: PatchError) -> Self {
+        Error::Patch(e)
+    }
+}
+
+impl From<hound::Error> for Error {
+    fn from(e: hound::Error) -> Self {
+        Error::Wav(e.to_string())
+    }
+}
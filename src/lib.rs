@@ -1,4 +1,10 @@
 pub mod app;
 pub mod components;
 pub mod core;
+pub mod error;
+pub mod midi;
+pub mod patch;
+pub mod render;
 pub mod testing;
+
+pub use error::{Error, Result};
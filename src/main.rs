@@ -1,20 +1,157 @@
-use anyhow::Result;
-use rynth::app::{audio_loop, configure_device, create_demo_engine};
+use anyhow::{Context, Result};
+use rynth::app::{
+    audio_loop, configure_device, list_output_devices, parse_args, select_host, AudioBackend,
+    BackendChoice, CliCommand, CpalBackend, EngineOverrides, NullBackend, RawPcmBackend,
+    WavBackend,
+};
+use rynth::patch::Patch;
+use rynth::render::{render_to_wav, RenderLength, RenderOptions};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
 use std::sync::mpsc::channel;
 use std::thread;
 use std::time::Duration;
 
+fn load_patch(path: &Path, overrides: &EngineOverrides) -> Result<Patch> {
+    let mut patch =
+        Patch::load(path).with_context(|| format!("failed to load {}", path.display()))?;
+    overrides.apply(&mut patch);
+    Ok(patch)
+}
+
+fn create_backend(
+    choice: BackendChoice,
+    jack: bool,
+    device: &str,
+    dither: bool,
+    length: Option<Duration>,
+) -> Result<Box<dyn AudioBackend>> {
+    Ok(match choice {
+        BackendChoice::Cpal => {
+            let mut backend = CpalBackend::new(configure_device(&select_host(jack)?, device)?);
+            backend.dither = dither;
+            Box::new(backend)
+        }
+        BackendChoice::Null => Box::new(NullBackend),
+        BackendChoice::Wav(path) => {
+            let mut backend = WavBackend::new(path);
+            backend.length = length;
+            Box::new(backend)
+        }
+        BackendChoice::Raw(Some(path)) => {
+            let mut backend = RawPcmBackend::new(BufWriter::new(
+                File::create(&path)
+                    .with_context(|| format!("failed to create {}", path.display()))?,
+            ));
+            backend.paced = true;
+            backend.length = length;
+            Box::new(backend)
+        }
+        BackendChoice::Raw(None) => Box::new(RawPcmBackend::new(std::io::stdout())),
+    })
+}
+
+fn list_devices(jack: bool) -> Result<()> {
+    let host = select_host(jack)?;
+    for device in list_output_devices(&host)? {
+        let default = if device.is_default { " (default)" } else { "" };
+        println!("{}{}", device.name, default);
+        for config in device.configs {
+            let buffer_size = match config.buffer_size() {
+                cpal::SupportedBufferSize::Range { min, max } => format!("{}-{}", min, max),
+                cpal::SupportedBufferSize::Unknown => "unknown".to_string(),
+            };
+            println!(
+                "  {} channels, {}-{} Hz, {:?}, buffer size {}",
+                config.channels(),
+                config.min_sample_rate().0,
+                config.max_sample_rate().0,
+                config.sample_format(),
+                buffer_size
+            );
+        }
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
-    let device = configure_device()?;
-    let (engine, topology) = create_demo_engine();
+    let command = parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit());
 
-    let (tx, rx) = channel();
+    match command {
+        CliCommand::ListDevices { jack } => list_devices(jack),
+        CliCommand::Play {
+            jack,
+            device,
+            patch,
+            engine,
+            duration,
+            backend,
+            dither,
+        } => {
+            let mut patch = load_patch(&patch, &engine)?;
+            // Files stop by themselves at the duration, so their length doesn't depend on how
+            // long this thread sleeps.
+            let writes_file =
+                matches!(backend, BackendChoice::Wav(_) | BackendChoice::Raw(Some(_)));
+            let mut backend = create_backend(backend, jack, &device, dither, duration)?;
+            if engine.sampling_rate.is_none() {
+                let preferred = backend.preferred_sampling_rate(&patch.engine_spec()?).0;
+                if preferred != patch.engine.sampling_rate {
+                    eprintln!(
+                        "the device doesn't support {} Hz, playing at {} Hz instead",
+                        patch.engine.sampling_rate, preferred
+                    );
+                    patch.engine.sampling_rate = preferred;
+                }
+            }
+            let engine = patch.create_engine()?;
+            let topology = patch.create_topology()?;
 
-    let handle = thread::spawn(move || audio_loop(engine, topology, device, rx));
-    thread::sleep(Duration::from_millis(10000));
+            // Playback lasts until the duration is up, Ctrl-C or the backend stops by itself, e.g.
+            // when the reader of a pipe goes away. Either way the backend gets to stop cleanly, so
+            // output files are finalized.
+            let (wake, woken) = channel();
+            let interrupt = wake.clone();
+            ctrlc::set_handler(move || {
+                let _ = interrupt.send(());
+            })
+            .context("failed to handle Ctrl-C")?;
 
-    drop(tx);
-    handle.join().unwrap().unwrap();
+            let (stop, stopped) = channel();
+            let handle = thread::spawn(move || {
+                let result = audio_loop(engine, topology, backend.as_mut(), stopped);
+                let _ = wake.send(());
+                result
+            });
+            match duration.filter(|_| !writes_file) {
+                Some(duration) => {
+                    let _ = woken.recv_timeout(duration);
+                }
+                None => {
+                    let _ = woken.recv();
+                }
+            }
+            drop(stop);
 
-    Ok(())
+            Ok(handle.join().unwrap()?)
+        }
+        CliCommand::Render {
+            patch,
+            output,
+            engine,
+            duration,
+            format,
+        } => {
+            let patch = load_patch(&patch, &engine)?;
+            let mut engine = patch.create_engine()?;
+            let mut topology = patch.create_topology()?;
+
+            let mut options = RenderOptions::new(RenderLength::Fixed(duration));
+            options.format = format;
+            render_to_wav(&mut engine, &mut topology, options, &output)
+                .with_context(|| format!("failed to render into {}", output.display()))?;
+            Ok(())
+        }
+    }
 }
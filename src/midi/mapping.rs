@@ -0,0 +1,211 @@
+use crate::core::commands::Command;
+use crate::core::reflection::ParameterId;
+use crate::core::topology::{AudioComponentId, AudioTopology};
+use crate::error::{Error, Result};
+use crate::midi::message::MidiMessage;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MidiControl {
+    Controller(u8),
+    PitchBend,
+    ChannelAftertouch,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct ControlMapping {
+    control: MidiControl,
+    parameter: ParameterId,
+    minimum_value: f32,
+    maximum_value: f32,
+}
+
+// Translates MIDI messages into engine commands. Notes go to a single component, and controls
+// are either mapped onto parameter ranges or forwarded to that component as control changes.
+pub struct MidiMapping {
+    pub channel: Option<u8>,
+    pub target: AudioComponentId,
+    controls: Vec<ControlMapping>,
+}
+
+impl MidiMapping {
+    pub fn new(target: AudioComponentId) -> Self {
+        Self {
+            channel: None,
+            target,
+            controls: vec![],
+        }
+    }
+
+    pub fn map_control(
+        &mut self,
+        control: MidiControl,
+        parameter: ParameterId,
+        minimum_value: f32,
+        maximum_value: f32,
+    ) {
+        self.controls.retain(|c| c.control != control);
+        self.controls.push(ControlMapping {
+            control,
+            parameter,
+            minimum_value,
+            maximum_value,
+        });
+    }
+
+    // Maps the control over the whole range of a parameter found by path, e.g. "osc1.frequency".
+    pub fn map_control_to_path(
+        &mut self,
+        topology: &AudioTopology,
+        control: MidiControl,
+        path: &str,
+    ) -> Result<()> {
+        let parameter = topology
+            .find_parameter(path)
+            .ok_or(Error::UnknownParameter)?;
+        let info = topology
+            .parameter_info(parameter)
+            .ok_or(Error::UnknownParameter)?;
+        self.map_control(control, parameter, info.minimum_value, info.maximum_value);
+        Ok(())
+    }
+
+    pub fn command(&self, message: &MidiMessage) -> Option<Command> {
+        if let (Some(channel), Some(message_channel)) = (self.channel, message.channel()) {
+            if channel != message_channel {
+                return None;
+            }
+        }
+
+        match *message {
+            // By convention a note on without velocity is a note off.
+            MidiMessage::NoteOn {
+                note, velocity: 0, ..
+            }
+            | MidiMessage::NoteOff { note, .. } => Some(Command::NoteOff {
+                component: self.target,
+                note,
+            }),
+            MidiMessage::NoteOn { note, velocity, .. } => Some(Command::NoteOn {
+                component: self.target,
+                note,
+                velocity,
+            }),
+            MidiMessage::ControlChange {
+                controller, value, ..
+            } => self
+                .control(MidiControl::Controller(controller), value as f32 / 127.0)
+                .or(Some(Command::ControlChange {
+                    component: self.target,
+                    controller,
+                    value,
+                })),
+            MidiMessage::PitchBend { value, .. } => {
+                self.control(MidiControl::PitchBend, value as f32 / 16383.0)
+            }
+            MidiMessage::ChannelAftertouch { pressure, .. } => {
+                self.control(MidiControl::ChannelAftertouch, pressure as f32 / 127.0)
+            }
+            _ => None,
+        }
+    }
+
+    fn control(&self, control: MidiControl, normalized: f32) -> Option<Command> {
+        let mapping = self.controls.iter().find(|c| c.control == control)?;
+        Some(Command::SetParameter {
+            parameter: mapping.parameter,
+            value: mapping.minimum_value
+                + (mapping.maximum_value - mapping.minimum_value) * normalized,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::concepts::{Channels, ModulationRate, SamplingRate};
+    use crate::core::empty_engine;
+    use crate::midi::parser::MidiParser;
+    use crate::testing::ConstantGenerator;
+
+    fn commands(mapping: &MidiMapping, bytes: &[u8]) -> Vec<Command> {
+        MidiParser::default()
+            .parse(bytes)
+            .filter_map(|m| mapping.command(&m))
+            .collect()
+    }
+
+    #[test]
+    fn maps_notes_and_controls() {
+        let (_, mut topology) =
+            empty_engine(SamplingRate(48000), ModulationRate(100), 128, Channels(1));
+        let generator_id = topology.add_component(ConstantGenerator::default());
+        topology.set_name(generator_id, "gen1");
+        let level = topology.find_parameter("gen1.level").unwrap();
+
+        let mut mapping = MidiMapping::new(generator_id);
+        mapping
+            .map_control_to_path(&topology, MidiControl::Controller(7), "gen1.level")
+            .unwrap();
+        mapping.map_control(MidiControl::PitchBend, level, 0.0, 1.0);
+
+        let bytes = [
+            0x90, 60, 100, 60, 0, // note on, then off through running status
+            0xB0, 7, 127, // mapped controller
+            0xB0, 1, 64, // unmapped controller
+            0xE0, 0x7F, 0x7F, // pitch bend up
+            0xC0, 3, // program changes aren't mapped
+        ];
+
+        assert_eq!(
+            commands(&mapping, &bytes),
+            vec![
+                Command::NoteOn {
+                    component: generator_id,
+                    note: 60,
+                    velocity: 100
+                },
+                Command::NoteOff {
+                    component: generator_id,
+                    note: 60
+                },
+                Command::SetParameter {
+                    parameter: level,
+                    value: 1.0
+                },
+                Command::ControlChange {
+                    component: generator_id,
+                    controller: 1,
+                    value: 64
+                },
+                Command::SetParameter {
+                    parameter: level,
+                    value: 1.0
+                },
+            ]
+        );
+        assert_eq!(
+            mapping.map_control_to_path(&topology, MidiControl::PitchBend, "gen1.pitch"),
+            Err(Error::UnknownParameter)
+        );
+    }
+
+    #[test]
+    fn filters_channels() {
+        let (_, mut topology) =
+            empty_engine(SamplingRate(48000), ModulationRate(100), 128, Channels(1));
+        let generator_id = topology.add_component(ConstantGenerator::default());
+
+        let mut mapping = MidiMapping::new(generator_id);
+        mapping.channel = Some(1);
+
+        let bytes = [0x90, 60, 100, 0x91, 62, 100, 0xF8];
+        assert_eq!(
+            commands(&mapping, &bytes),
+            vec![Command::NoteOn {
+                component: generator_id,
+                note: 62,
+                velocity: 100
+            }]
+        );
+    }
+}
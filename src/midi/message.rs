@@ -0,0 +1,112 @@
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MidiMessage {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    PolyAftertouch {
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ChannelAftertouch {
+        channel: u8,
+        pressure: u8,
+    },
+    // 14 bits, centered at 8192.
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+    Clock,
+    Start,
+    Continue,
+    Stop,
+    ActiveSensing,
+    Reset,
+}
+
+pub const PITCH_BEND_CENTER: u16 = 8192;
+
+impl MidiMessage {
+    pub fn channel(&self) -> Option<u8> {
+        match *self {
+            MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::PolyAftertouch { channel, .. }
+            | MidiMessage::ControlChange { channel, .. }
+            | MidiMessage::ProgramChange { channel, .. }
+            | MidiMessage::ChannelAftertouch { channel, .. }
+            | MidiMessage::PitchBend { channel, .. } => Some(channel),
+            _ => None,
+        }
+    }
+
+    // Builds a channel message from its status byte and data bytes.
+    pub fn from_channel_bytes(status: u8, data: &[u8]) -> Option<Self> {
+        let channel = status & 0x0F;
+        let message = match (status & 0xF0, data) {
+            (0x80, [note, velocity]) => MidiMessage::NoteOff {
+                channel,
+                note: *note,
+                velocity: *velocity,
+            },
+            (0x90, [note, velocity]) => MidiMessage::NoteOn {
+                channel,
+                note: *note,
+                velocity: *velocity,
+            },
+            (0xA0, [note, pressure]) => MidiMessage::PolyAftertouch {
+                channel,
+                note: *note,
+                pressure: *pressure,
+            },
+            (0xB0, [controller, value]) => MidiMessage::ControlChange {
+                channel,
+                controller: *controller,
+                value: *value,
+            },
+            (0xC0, [program]) => MidiMessage::ProgramChange {
+                channel,
+                program: *program,
+            },
+            (0xD0, [pressure]) => MidiMessage::ChannelAftertouch {
+                channel,
+                pressure: *pressure,
+            },
+            (0xE0, [lsb, msb]) => MidiMessage::PitchBend {
+                channel,
+                value: (*msb as u16) << 7 | *lsb as u16,
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    pub fn from_realtime_byte(status: u8) -> Option<Self> {
+        match status {
+            0xF8 => Some(MidiMessage::Clock),
+            0xFA => Some(MidiMessage::Start),
+            0xFB => Some(MidiMessage::Continue),
+            0xFC => Some(MidiMessage::Stop),
+            0xFE => Some(MidiMessage::ActiveSensing),
+            0xFF => Some(MidiMessage::Reset),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,11 @@
+mod mapping;
+mod message;
+mod parser;
+mod player;
+mod smf;
+
+pub use mapping::*;
+pub use message::*;
+pub use parser::*;
+pub use player::*;
+pub use smf::*;
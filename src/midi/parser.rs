@@ -0,0 +1,214 @@
+use crate::midi::message::MidiMessage;
+
+const SYSEX_START: u8 = 0xF0;
+
+// Number of data bytes following a status byte.
+fn data_length(status: u8) -> usize {
+    match status {
+        0x80..=0xBF | 0xE0..=0xEF => 2,
+        0xC0..=0xDF => 1,
+        0xF1 | 0xF3 => 1,
+        0xF2 => 2,
+        _ => 0,
+    }
+}
+
+// Turns a MIDI 1.0 byte stream into messages, one byte at a time. System exclusive and system
+// common messages are skipped.
+#[derive(Default)]
+pub struct MidiParser {
+    status: Option<u8>,
+    data: [u8; 2],
+    data_received: usize,
+    in_sysex: bool,
+}
+
+impl MidiParser {
+    pub fn feed(&mut self, byte: u8) -> Option<MidiMessage> {
+        // Realtime messages may show up anywhere, even in the middle of other messages.
+        if byte >= 0xF8 {
+            return MidiMessage::from_realtime_byte(byte);
+        }
+
+        if byte & 0x80 != 0 {
+            self.in_sysex = byte == SYSEX_START;
+            self.status = match byte {
+                0x80..=0xEF | 0xF1..=0xF3 => Some(byte),
+                _ => None,
+            };
+            self.data_received = 0;
+            return None;
+        }
+
+        if self.in_sysex {
+            return None;
+        }
+        let status = self.status?;
+
+        self.data[self.data_received] = byte;
+        self.data_received += 1;
+        if self.data_received < data_length(status) {
+            return None;
+        }
+        self.data_received = 0;
+
+        if status >= 0xF0 {
+            // System common messages cancel running status.
+            self.status = None;
+            return None;
+        }
+        MidiMessage::from_channel_bytes(status, &self.data[..data_length(status)])
+    }
+
+    pub fn parse<'a>(&'a mut self, bytes: &'a [u8]) -> impl Iterator<Item = MidiMessage> + 'a {
+        bytes.iter().filter_map(move |byte| self.feed(*byte))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<MidiMessage> {
+        MidiParser::default().parse(bytes).collect()
+    }
+
+    #[test]
+    fn parses_channel_messages() {
+        let bytes = [
+            0x90, 60, 100, // note on
+            0x81, 60, 64, // note off
+            0xA2, 61, 30, // poly aftertouch
+            0xB3, 1, 127, // control change
+            0xC4, 5, // program change
+            0xD5, 90, // channel aftertouch
+            0xE6, 0x00, 0x40, // pitch bend, centered
+        ];
+
+        assert_eq!(
+            parse(&bytes),
+            vec![
+                MidiMessage::NoteOn {
+                    channel: 0,
+                    note: 60,
+                    velocity: 100
+                },
+                MidiMessage::NoteOff {
+                    channel: 1,
+                    note: 60,
+                    velocity: 64
+                },
+                MidiMessage::PolyAftertouch {
+                    channel: 2,
+                    note: 61,
+                    pressure: 30
+                },
+                MidiMessage::ControlChange {
+                    channel: 3,
+                    controller: 1,
+                    value: 127
+                },
+                MidiMessage::ProgramChange {
+                    channel: 4,
+                    program: 5
+                },
+                MidiMessage::ChannelAftertouch {
+                    channel: 5,
+                    pressure: 90
+                },
+                MidiMessage::PitchBend {
+                    channel: 6,
+                    value: 8192
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_running_status() {
+        let bytes = [0x90, 60, 100, 64, 100, 67, 0];
+
+        let notes: Vec<_> = parse(&bytes)
+            .into_iter()
+            .map(|m| match m {
+                MidiMessage::NoteOn { note, velocity, .. } => (note, velocity),
+                _ => panic!("unexpected message {:?}", m),
+            })
+            .collect();
+        assert_eq!(notes, vec![(60, 100), (64, 100), (67, 0)]);
+    }
+
+    #[test]
+    fn skips_sysex_and_system_common_messages() {
+        let bytes = [
+            0xB0, 7, 100, // volume
+            0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7, // general MIDI on
+            10, 64, // no running status after sysex
+            0xF2, 0x10, 0x20, // song position
+            0xF6, // tune request
+            0xC0, 3, 4, // running status program changes
+        ];
+
+        assert_eq!(
+            parse(&bytes),
+            vec![
+                MidiMessage::ControlChange {
+                    channel: 0,
+                    controller: 7,
+                    value: 100
+                },
+                MidiMessage::ProgramChange {
+                    channel: 0,
+                    program: 3
+                },
+                MidiMessage::ProgramChange {
+                    channel: 0,
+                    program: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn interleaves_realtime_messages() {
+        let bytes = [
+            0xFA, // start
+            0x90, 0xF8, 60, 0xF8, 100, // clocks in the middle of a note on
+            0xF0, 0x01, 0xFE, 0x02, 0xF7, // active sensing inside sysex
+            0xFC, // stop
+        ];
+
+        assert_eq!(
+            parse(&bytes),
+            vec![
+                MidiMessage::Start,
+                MidiMessage::Clock,
+                MidiMessage::Clock,
+                MidiMessage::NoteOn {
+                    channel: 0,
+                    note: 60,
+                    velocity: 100
+                },
+                MidiMessage::ActiveSensing,
+                MidiMessage::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn resumes_after_fragmented_input() {
+        let mut parser = MidiParser::default();
+
+        let first: Vec<_> = parser.parse(&[0xE0, 0x7F]).collect();
+        let second: Vec<_> = parser.parse(&[0x7F]).collect();
+
+        assert!(first.is_empty());
+        assert_eq!(
+            second,
+            vec![MidiMessage::PitchBend {
+                channel: 0,
+                value: 16383
+            }]
+        );
+    }
+}
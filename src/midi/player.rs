@@ -0,0 +1,116 @@
+use crate::core::commands::TimedCommand;
+use crate::core::concepts::{AudioSampleIndex, SamplingRate};
+use crate::core::engine::Engine;
+use crate::core::topology::AudioTopology;
+use crate::midi::mapping::MidiMapping;
+use crate::midi::message::MidiMessage;
+use crate::midi::smf::MidiFile;
+
+// Feeds the events of a MIDI file to an engine block by block, at sample accurate offsets.
+pub struct MidiPlayer {
+    events: Vec<(AudioSampleIndex, MidiMessage)>,
+    end: AudioSampleIndex,
+    next_event: usize,
+    mapping: MidiMapping,
+    block_events: Vec<TimedCommand>,
+}
+
+impl MidiPlayer {
+    pub fn new(file: &MidiFile, sampling_rate: SamplingRate, mapping: MidiMapping) -> Self {
+        let to_sample = |time: std::time::Duration| {
+            AudioSampleIndex((time.as_secs_f64() * sampling_rate.0 as f64).round() as u64)
+        };
+        let events: Vec<_> = file
+            .events
+            .iter()
+            .map(|e| (to_sample(e.time), e.message))
+            .collect();
+
+        Self {
+            block_events: Vec::with_capacity(events.len()),
+            events,
+            end: to_sample(file.duration),
+            next_event: 0,
+            mapping,
+        }
+    }
+
+    pub fn end(&self) -> AudioSampleIndex {
+        self.end
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_event == self.events.len()
+    }
+
+    // Events are placed relative to the engine clock, so playback starts at the engine's sample 0.
+    pub fn advance(
+        &mut self,
+        engine: &mut Engine,
+        topology: &mut AudioTopology,
+        audio: &mut [f32],
+    ) {
+        let start = engine.current_sample();
+        let frames = audio.len() / engine.spec.channels.0 as usize;
+        let block_end = AudioSampleIndex(start.0 + frames as u64);
+
+        self.block_events.clear();
+        while let Some((sample, message)) = self.events.get(self.next_event) {
+            if *sample >= block_end {
+                break;
+            }
+            if let Some(command) = self.mapping.command(message) {
+                // Events the engine has already passed are applied at the start of the block.
+                self.block_events.push(TimedCommand {
+                    offset: *sample.max(&start) - start,
+                    command,
+                });
+            }
+            self.next_event += 1;
+        }
+
+        engine.advance_with_events(topology, audio, &self.block_events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::concepts::{Channels, ModulationRate};
+    use crate::core::empty_engine;
+    use crate::midi::smf::tests::smf_bytes;
+    use crate::testing::NoteGenerator;
+
+    #[test]
+    fn plays_notes_at_file_times() {
+        let (mut engine, mut topology) =
+            empty_engine(SamplingRate(1000), ModulationRate(100), 64, Channels(1));
+        let generator_id = topology.add_component(NoteGenerator::default());
+
+        // 10 ticks per beat at 120 bpm makes a tick 50 samples long.
+        let track = [
+            0x01, 0x90, 50, 127, //
+            0x02, 0x80, 50, 0, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let file = MidiFile::parse(&smf_bytes(0, 10, &[&track])).unwrap();
+        let mut player = MidiPlayer::new(
+            &file,
+            engine.spec.sampling_rate,
+            MidiMapping::new(generator_id),
+        );
+        assert_eq!(player.end(), AudioSampleIndex(150));
+
+        let mut obtained = vec![f32::NAN; 192];
+        for block in obtained.chunks_mut(64) {
+            player.advance(&mut engine, &mut topology, block);
+        }
+
+        let level = 50.0 / 100.0 * 127.0 / 127.0;
+        let expected: Vec<f32> = (0..192)
+            .map(|s| if (50..150).contains(&s) { level } else { 0.0 })
+            .collect();
+        assert_eq!(obtained, expected);
+        assert!(player.is_finished());
+    }
+}
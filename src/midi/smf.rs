@@ -0,0 +1,233 @@
+use crate::error::{Error, Result};
+use crate::midi::message::MidiMessage;
+use midly::{Format, MetaMessage, Smf, Timing, TrackEventKind};
+use std::path::Path;
+use std::time::Duration;
+
+// Tempo assumed by the standard until the first tempo event, 120 beats per minute.
+const DEFAULT_MICROSECONDS_PER_BEAT: u32 = 500_000;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MidiFileEvent {
+    pub time: Duration,
+    pub message: MidiMessage,
+}
+
+// The channel messages of a Standard MIDI File, merged across tracks and timed through its
+// tempo map.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MidiFile {
+    pub events: Vec<MidiFileEvent>,
+    // Includes trailing meta events, so it may be longer than the last message.
+    pub duration: Duration,
+}
+
+impl MidiFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| Error::Io(e.to_string()))?;
+        Self::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let smf = Smf::parse(bytes).map_err(|e| Error::MidiFile(e.to_string()))?;
+        if smf.header.format == Format::Sequential {
+            return Err(Error::MidiFile(
+                "sequential (format 2) files aren't supported".to_string(),
+            ));
+        }
+
+        // Tracks play in parallel, so everything is merged on absolute ticks. The sort is
+        // stable, which keeps tempo changes of the first track ahead of notes on the same tick.
+        let mut track_events = vec![];
+        for track in &smf.tracks {
+            let mut tick = 0u64;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                track_events.push((tick, event.kind));
+            }
+        }
+        track_events.sort_by_key(|(tick, _)| *tick);
+
+        let mut clock = TempoClock::new(smf.header.timing);
+        let mut events = vec![];
+        for (tick, kind) in track_events {
+            let time = clock.time_at(tick);
+            match kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => clock.set_tempo(tempo.as_int()),
+                TrackEventKind::Midi { channel, message } => events.push(MidiFileEvent {
+                    time: Duration::from_secs_f64(time),
+                    message: convert_message(channel.as_int(), message),
+                }),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            events,
+            duration: Duration::from_secs_f64(clock.seconds),
+        })
+    }
+}
+
+struct TempoClock {
+    timing: Timing,
+    microseconds_per_beat: u32,
+    tick: u64,
+    seconds: f64,
+}
+
+impl TempoClock {
+    fn new(timing: Timing) -> Self {
+        Self {
+            timing,
+            microseconds_per_beat: DEFAULT_MICROSECONDS_PER_BEAT,
+            tick: 0,
+            seconds: 0.0,
+        }
+    }
+
+    // Ticks have to be visited in order, since a tempo change only affects what comes after it.
+    fn time_at(&mut self, tick: u64) -> f64 {
+        self.seconds += (tick - self.tick) as f64 * self.seconds_per_tick();
+        self.tick = tick;
+        self.seconds
+    }
+
+    fn set_tempo(&mut self, microseconds_per_beat: u32) {
+        self.microseconds_per_beat = microseconds_per_beat;
+    }
+
+    fn seconds_per_tick(&self) -> f64 {
+        match self.timing {
+            Timing::Metrical(ticks_per_beat) => {
+                self.microseconds_per_beat as f64 / 1e6 / ticks_per_beat.as_int().max(1) as f64
+            }
+            // Timecode based files ignore tempo events.
+            Timing::Timecode(fps, ticks_per_frame) => {
+                1.0 / (fps.as_f32() as f64 * ticks_per_frame.max(1) as f64)
+            }
+        }
+    }
+}
+
+fn convert_message(channel: u8, message: midly::MidiMessage) -> MidiMessage {
+    match message {
+        midly::MidiMessage::NoteOff { key, vel } => MidiMessage::NoteOff {
+            channel,
+            note: key.as_int(),
+            velocity: vel.as_int(),
+        },
+        midly::MidiMessage::NoteOn { key, vel } => MidiMessage::NoteOn {
+            channel,
+            note: key.as_int(),
+            velocity: vel.as_int(),
+        },
+        midly::MidiMessage::Aftertouch { key, vel } => MidiMessage::PolyAftertouch {
+            channel,
+            note: key.as_int(),
+            pressure: vel.as_int(),
+        },
+        midly::MidiMessage::Controller { controller, value } => MidiMessage::ControlChange {
+            channel,
+            controller: controller.as_int(),
+            value: value.as_int(),
+        },
+        midly::MidiMessage::ProgramChange { program } => MidiMessage::ProgramChange {
+            channel,
+            program: program.as_int(),
+        },
+        midly::MidiMessage::ChannelAftertouch { vel } => MidiMessage::ChannelAftertouch {
+            channel,
+            pressure: vel.as_int(),
+        },
+        midly::MidiMessage::PitchBend { bend } => MidiMessage::PitchBend {
+            channel,
+            value: bend.0.as_int(),
+        },
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn smf_bytes(format: u16, ticks_per_beat: u16, tracks: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend_from_slice(&6u32.to_be_bytes());
+        bytes.extend_from_slice(&format.to_be_bytes());
+        bytes.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&ticks_per_beat.to_be_bytes());
+        for track in tracks {
+            bytes.extend_from_slice(b"MTrk");
+            bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(track);
+        }
+        bytes
+    }
+
+    fn times(file: &MidiFile) -> Vec<f64> {
+        file.events.iter().map(|e| e.time.as_secs_f64()).collect()
+    }
+
+    #[test]
+    fn times_single_track_files_at_default_tempo() {
+        let track = [
+            0x00, 0x90, 60, 100, // note on
+            0x83, 0x60, 0x80, 60, 0, // note off a beat later (480 ticks)
+            0x00, 0xFF, 0x2F, 0x00, // end of track
+        ];
+        let file = MidiFile::parse(&smf_bytes(0, 480, &[&track])).unwrap();
+
+        assert_eq!(
+            file.events,
+            vec![
+                MidiFileEvent {
+                    time: Duration::from_secs(0),
+                    message: MidiMessage::NoteOn {
+                        channel: 0,
+                        note: 60,
+                        velocity: 100
+                    }
+                },
+                MidiFileEvent {
+                    time: Duration::from_millis(500),
+                    message: MidiMessage::NoteOff {
+                        channel: 0,
+                        note: 60,
+                        velocity: 0
+                    }
+                },
+            ]
+        );
+        assert_eq!(file.duration, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn follows_tempo_map_across_tracks() {
+        let tempo_track = [
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 500000 us per beat
+            0x83, 0x60, 0xFF, 0x51, 0x03, 0x03, 0xD0, 0x90, // 250000 us per beat after a beat
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let note_track = [
+            0x00, 0x91, 60, 100, // channel 1
+            0x83, 0x60, 0x91, 62, 100, // on the tempo change
+            0x83, 0x60, 0x81, 62, 0, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let file = MidiFile::parse(&smf_bytes(1, 480, &[&tempo_track, &note_track])).unwrap();
+
+        assert_eq!(times(&file), vec![0.0, 0.5, 0.75]);
+        assert!(file.events.iter().all(|e| e.message.channel() == Some(1)));
+    }
+
+    #[test]
+    fn rejects_sequential_and_malformed_files() {
+        let track = [0x00, 0xFF, 0x2F, 0x00];
+        assert!(matches!(
+            MidiFile::parse(&smf_bytes(2, 480, &[&track])),
+            Err(Error::MidiFile(_))
+        ));
+        assert!(matches!(MidiFile::parse(b"MThd"), Err(Error::MidiFile(_))));
+    }
+}
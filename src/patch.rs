@@ -0,0 +1,735 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Envelope, LowFrequencyOscillator, Oscillator};
+use crate::core::{
+    AudioComponentId, AudioInputIndex, AudioTopology, Channels, DynAudioComponent,
+    DynModulationComponent, Engine, EngineSpec, ModulationCurve, ModulationMapping, ModulationRate,
+    ModulationUnit, Parameter, ParameterId, ParameterOwner, Parameterized, Polarity, SamplingRate,
+};
+use crate::error::{Error, Result};
+
+#[derive(PartialEq, Debug)]
+pub enum PatchError {
+    Syntax(String),
+    UnknownComponentType(String),
+    UnknownName(String),
+    DuplicateName(String),
+    UnknownParameter(String),
+    ParameterOutOfRange {
+        path: String,
+        value: f32,
+        minimum_value: f32,
+        maximum_value: f32,
+    },
+    UnsupportedComponent,
+    TooManyModulations(String),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Syntax(message) => write!(f, "invalid patch: {}", message),
+            PatchError::UnknownComponentType(name) => {
+                write!(f, "unknown component type \"{}\"", name)
+            }
+            PatchError::UnknownName(name) => write!(f, "no component is named \"{}\"", name),
+            PatchError::DuplicateName(name) => {
+                write!(f, "more than one component is named \"{}\"", name)
+            }
+            PatchError::UnknownParameter(path) => write!(f, "unknown parameter \"{}\"", path),
+            PatchError::ParameterOutOfRange {
+                path,
+                value,
+                minimum_value,
+                maximum_value,
+            } => write!(
+                f,
+                "value {} of \"{}\" is outside of [{}, {}]",
+                value, path, minimum_value, maximum_value
+            ),
+            PatchError::UnsupportedComponent => {
+                write!(f, "topology contains a component that can't be saved")
+            }
+            PatchError::TooManyModulations(path) => {
+                write!(f, "\"{}\" has more modulations than it can take", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EnginePatch {
+    pub sampling_rate: u32,
+    pub modulation_rate: u32,
+    pub channels: u16,
+    pub max_samples_per_step: usize,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ModulatorPatch {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, f32>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ComponentPatch {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    // Components are mixed into the engine output unless they only feed other components.
+    #[serde(default = "default_output")]
+    pub output: bool,
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+    #[serde(default)]
+    pub pan: f32,
+    #[serde(default)]
+    pub bypassed: bool,
+    // Last, since TOML tables have to follow plain values.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, f32>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ConnectionPatch {
+    pub source: String,
+    pub destination: String,
+    #[serde(default)]
+    pub input: usize,
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+}
+
+// Without an offset, unipolar range modulations start at the minimum of the parameter like
+// `add_modulation`, and other modulations are centred on the parameter value.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ModulationPatch {
+    pub parameter: String,
+    pub modulator: String,
+    pub level: f32,
+    #[serde(default = "default_polarity")]
+    pub polarity: Polarity,
+    #[serde(default = "default_curve")]
+    pub curve: ModulationCurve,
+    #[serde(default = "default_unit")]
+    pub unit: ModulationUnit,
+    pub offset: Option<f32>,
+}
+
+impl ModulationPatch {
+    fn mapping(&self, parameter: &Parameter) -> ModulationMapping {
+        let offset = match (self.offset, self.unit, self.polarity) {
+            (Some(offset), _, _) => offset,
+            (None, ModulationUnit::Range, Polarity::Unipolar) => {
+                parameter.minimum_value() * self.level
+            }
+            (None, _, _) => 0.0,
+        };
+        ModulationMapping {
+            polarity: self.polarity,
+            curve: self.curve,
+            unit: self.unit,
+            offset,
+        }
+    }
+}
+
+// A text description of an engine configuration and a topology. Components, modulators and
+// modulation routings refer to each other by name, and parameters by "name.parameter" paths.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Patch {
+    pub engine: EnginePatch,
+    #[serde(default)]
+    pub modulators: Vec<ModulatorPatch>,
+    #[serde(default)]
+    pub components: Vec<ComponentPatch>,
+    #[serde(default)]
+    pub connections: Vec<ConnectionPatch>,
+    #[serde(default)]
+    pub modulations: Vec<ModulationPatch>,
+}
+
+fn default_output() -> bool {
+    true
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+fn default_polarity() -> Polarity {
+    Polarity::Unipolar
+}
+
+fn default_curve() -> ModulationCurve {
+    ModulationCurve::Linear
+}
+
+fn default_unit() -> ModulationUnit {
+    ModulationUnit::Range
+}
+
+fn create_component(type_name: &str, spec: &EngineSpec) -> Option<DynAudioComponent> {
+    match type_name {
+        "oscillator" => Some(Box::new(Oscillator::new(440.0, spec.sampling_rate))),
+        "envelope" => Some(Box::new(Envelope::new(spec.sampling_rate))),
+        _ => None,
+    }
+}
+
+fn create_modulator(type_name: &str, spec: &EngineSpec) -> Option<DynModulationComponent> {
+    match type_name {
+        "lfo" => Some(Box::new(LowFrequencyOscillator::new(
+            1.0,
+            spec.modulation_rate,
+        ))),
+        _ => None,
+    }
+}
+
+impl Patch {
+    pub fn from_toml(text: &str) -> Result<Self> {
+        toml::from_str(text).map_err(|e| PatchError::Syntax(e.to_string()).into())
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|e| PatchError::Syntax(e.to_string()).into())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| Error::Io(e.to_string()))?;
+        Self::from_toml(&text)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_toml()?).map_err(|e| Error::Io(e.to_string()))
+    }
+
+    pub fn engine_spec(&self) -> Result<EngineSpec> {
+        EngineSpec::try_new(
+            SamplingRate(self.engine.sampling_rate),
+            ModulationRate(self.engine.modulation_rate),
+            Channels(self.engine.channels),
+            self.engine.max_samples_per_step,
+        )
+    }
+
+    pub fn create_engine(&self) -> Result<Engine> {
+        Ok(Engine::new(self.engine_spec()?))
+    }
+
+    pub fn create_topology(&self) -> Result<AudioTopology> {
+        let spec = self.engine_spec()?;
+        let mut topology = AudioTopology::new(spec);
+
+        for modulator in &self.modulators {
+            check_unique_name(&topology, &modulator.name)?;
+            let component = create_modulator(&modulator.type_name, &spec)
+                .ok_or_else(|| PatchError::UnknownComponentType(modulator.type_name.clone()))?;
+            let id = topology.add_boxed_modulator(component)?;
+            topology.set_name(id, &modulator.name);
+            set_parameters(&mut topology, &modulator.name, &modulator.parameters)?;
+        }
+
+        for component in &self.components {
+            check_unique_name(&topology, &component.name)?;
+            let boxed = create_component(&component.type_name, &spec)
+                .ok_or_else(|| PatchError::UnknownComponentType(component.type_name.clone()))?;
+            let id = topology.add_boxed_component(boxed);
+            topology.set_name(id, &component.name);
+            set_parameters(&mut topology, &component.name, &component.parameters)?;
+
+            match component.output {
+                true => {
+                    topology.connect_to_output(id, component.gain);
+                    if let Some(input) = topology.get_mixer_input_mut(id) {
+                        input.pan = component.pan;
+                    }
+                }
+                false => topology.disconnect_from_output(id),
+            }
+            topology.set_bypass(id, component.bypassed)?;
+        }
+
+        for connection in &self.connections {
+            let source = find_component(&topology, &connection.source)?;
+            let destination = find_component(&topology, &connection.destination)?;
+            topology.connect(
+                source,
+                destination,
+                AudioInputIndex(connection.input),
+                connection.gain,
+            )?;
+        }
+
+        for modulation in &self.modulations {
+            let modulator = match topology.find_owner(&modulation.modulator) {
+                Some(ParameterOwner::Modulation(id)) => id,
+                _ => return Err(PatchError::UnknownName(modulation.modulator.clone()).into()),
+            };
+            let parameter_id = topology
+                .find_parameter(&modulation.parameter)
+                .ok_or_else(|| PatchError::UnknownParameter(modulation.parameter.clone()))?;
+            let parameter = topology
+                .get_parameter(parameter_id)
+                .ok_or_else(|| PatchError::UnknownParameter(modulation.parameter.clone()))?;
+
+            let mapping = modulation.mapping(parameter);
+            topology
+                .add_modulation(parameter_id, modulator, modulation.level, mapping)
+                .map_err(|e| match e {
+                    Error::TooManyModulations => {
+                        PatchError::TooManyModulations(modulation.parameter.clone()).into()
+                    }
+                    e => e,
+                })?;
+        }
+
+        Ok(topology)
+    }
+
+    // Unnamed components are given names derived from their type.
+    pub fn from_topology(topology: &AudioTopology) -> Result<Self> {
+        let mut names = Names::new(topology);
+        let spec = topology.spec;
+
+        let mut modulators = vec![];
+        for id in topology.modulation_components.iter_ids() {
+            let modulator = topology.get_modulator(id).expect("id is from the store");
+            let type_name = modulator
+                .type_name()
+                .ok_or(PatchError::UnsupportedComponent)?;
+            modulators.push(ModulatorPatch {
+                name: names.name(topology, id.into(), type_name),
+                type_name: type_name.to_string(),
+                parameters: parameter_values(topology, id.into()),
+            });
+        }
+
+        let mut components = vec![];
+        for id in topology.audio_components.iter_ids() {
+            let component = topology
+                .audio_components
+                .get_component(id)
+                .expect("id is from the store");
+            let type_name = component
+                .type_name()
+                .ok_or(PatchError::UnsupportedComponent)?;
+            let mixer_input = topology.mixer.iter_inputs().find(|i| i.component == id);
+            components.push(ComponentPatch {
+                name: names.name(topology, id.into(), type_name),
+                type_name: type_name.to_string(),
+                parameters: parameter_values(topology, id.into()),
+                output: mixer_input.is_some(),
+                gain: mixer_input.map_or(1.0, |i| i.gain),
+                pan: mixer_input.map_or(0.0, |i| i.pan),
+                bypassed: topology.is_bypassed(id).unwrap_or(false),
+            });
+        }
+
+        let connections = topology
+            .routing
+            .iter_connections()
+            .map(|c| ConnectionPatch {
+                source: names.get(c.source.into()),
+                destination: names.get(c.destination.into()),
+                input: c.input.0,
+                gain: c.gain,
+            })
+            .collect();
+
+        let mut modulations = vec![];
+        for (path, parameter_id) in names.parameter_paths(topology) {
+            let parameter = match topology.get_parameter(parameter_id) {
+                Some(parameter) => parameter,
+                None => continue,
+            };
+            for (modulator, level, mapping) in parameter.iter_modulations() {
+                modulations.push(ModulationPatch {
+                    parameter: path.clone(),
+                    modulator: names.get(modulator.into()),
+                    level,
+                    polarity: mapping.polarity,
+                    curve: mapping.curve,
+                    unit: mapping.unit,
+                    offset: Some(mapping.offset),
+                });
+            }
+        }
+
+        Ok(Self {
+            engine: EnginePatch {
+                sampling_rate: spec.sampling_rate.0,
+                modulation_rate: spec.modulation_rate.0,
+                channels: spec.channels.0,
+                max_samples_per_step: spec.max_samples_per_step,
+            },
+            modulators,
+            components,
+            connections,
+            modulations,
+        })
+    }
+}
+
+fn check_unique_name(topology: &AudioTopology, name: &str) -> Result<()> {
+    match topology.find_owner(name) {
+        Some(_) => Err(PatchError::DuplicateName(name.to_string()).into()),
+        None => Ok(()),
+    }
+}
+
+fn find_component(topology: &AudioTopology, name: &str) -> Result<AudioComponentId> {
+    match topology.find_owner(name) {
+        Some(ParameterOwner::Audio(id)) => Ok(id),
+        _ => Err(PatchError::UnknownName(name.to_string()).into()),
+    }
+}
+
+fn set_parameters(
+    topology: &mut AudioTopology,
+    name: &str,
+    parameters: &BTreeMap<String, f32>,
+) -> Result<()> {
+    for (parameter_name, value) in parameters {
+        let path = format!("{}.{}", name, parameter_name);
+        let parameter = topology
+            .find_parameter(&path)
+            .and_then(|id| topology.get_parameter_mut(id))
+            .ok_or_else(|| PatchError::UnknownParameter(path.clone()))?;
+        parameter
+            .try_set_value(*value)
+            .map_err(|_| PatchError::ParameterOutOfRange {
+                path,
+                value: *value,
+                minimum_value: parameter.minimum_value(),
+                maximum_value: parameter.maximum_value(),
+            })?;
+    }
+    Ok(())
+}
+
+fn parameter_values(topology: &AudioTopology, owner: ParameterOwner) -> BTreeMap<String, f32> {
+    let component: &dyn Parameterized = match owner {
+        ParameterOwner::Audio(id) => match topology.audio_components.get_component(id) {
+            Some(component) => component,
+            None => return BTreeMap::new(),
+        },
+        ParameterOwner::Modulation(id) => match topology.get_modulator(id) {
+            Some(modulator) => modulator,
+            None => return BTreeMap::new(),
+        },
+    };
+    component
+        .parameter_descriptors()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, descriptor)| {
+            let value = component.parameter(index)?.get_value();
+            Some((descriptor.name.to_string(), value))
+        })
+        .collect()
+}
+
+// Names used while saving, including the ones made up for unnamed components.
+struct Names {
+    names: Vec<(String, ParameterOwner)>,
+}
+
+impl Names {
+    fn new(topology: &AudioTopology) -> Self {
+        let mut names = vec![];
+        for id in topology.modulation_components.iter_ids() {
+            if let Some(name) = topology.get_name(id) {
+                names.push((name.to_string(), id.into()));
+            }
+        }
+        for id in topology.audio_components.iter_ids() {
+            if let Some(name) = topology.get_name(id) {
+                names.push((name.to_string(), id.into()));
+            }
+        }
+        Self { names }
+    }
+
+    fn name(&mut self, topology: &AudioTopology, owner: ParameterOwner, type_name: &str) -> String {
+        if let Some(name) = topology.get_name(owner) {
+            return name.to_string();
+        }
+        let name = (1..)
+            .map(|n| format!("{}{}", type_name, n))
+            .find(|candidate| self.names.iter().all(|(n, _)| n != candidate))
+            .expect("there's always an unused name");
+        self.names.push((name.clone(), owner));
+        name
+    }
+
+    // Only called once every component has been named.
+    fn get(&self, owner: ParameterOwner) -> String {
+        self.names
+            .iter()
+            .find(|(_, o)| *o == owner)
+            .map(|(n, _)| n.clone())
+            .unwrap_or_default()
+    }
+
+    fn parameter_paths(&self, topology: &AudioTopology) -> Vec<(String, ParameterId)> {
+        let mut paths = vec![];
+        for (name, owner) in &self.names {
+            let descriptors = match *owner {
+                ParameterOwner::Audio(id) => topology
+                    .audio_components
+                    .get_component(id)
+                    .map(|c| c.parameter_descriptors()),
+                ParameterOwner::Modulation(id) => topology
+                    .get_modulator(id)
+                    .map(|c| c.parameter_descriptors()),
+            };
+            for (index, descriptor) in descriptors.unwrap_or(&[]).iter().enumerate() {
+                paths.push((
+                    format!("{}.{}", name, descriptor.name),
+                    ParameterId {
+                        owner: *owner,
+                        index,
+                    },
+                ));
+            }
+        }
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{create_demo_engine, create_demo_topology};
+    use crate::core::{AudioSampleIndex, ModulationComponentsStore, MODULATION_CAPACITY};
+    use crate::testing::{AlternatingModulator, ConstantGenerator};
+
+    const DEMO_PATCH: &str = include_str!("../patches/demo.toml");
+
+    fn render(engine: &mut Engine, topology: &mut AudioTopology, samples: usize) -> Vec<f32> {
+        let mut output = vec![0.0; samples * engine.spec.channels.0 as usize];
+        for block in
+            output.chunks_mut(engine.spec.max_samples_per_step * engine.spec.channels.0 as usize)
+        {
+            engine.advance(topology, block);
+        }
+        output
+    }
+
+    #[test]
+    fn loads_the_demo_patch() {
+        let patch = Patch::from_toml(DEMO_PATCH).unwrap();
+        let mut engine = patch.create_engine().unwrap();
+        let mut topology = patch.create_topology().unwrap();
+
+        let (mut demo_engine, _) = create_demo_engine().unwrap();
+        let mut demo_topology = create_demo_topology(&demo_engine, 500.0).unwrap();
+
+        assert_eq!(
+            render(&mut engine, &mut topology, 4800),
+            render(&mut demo_engine, &mut demo_topology, 4800)
+        );
+    }
+
+    #[test]
+    fn saves_and_reloads_topologies() {
+        let (engine, _) = create_demo_engine().unwrap();
+        let mut topology = create_demo_topology(&engine, 500.0).unwrap();
+        let envelope = topology.add_component(Envelope::new(engine.spec.sampling_rate));
+        let oscillator = topology.find_owner("osc1").unwrap();
+        if let ParameterOwner::Audio(oscillator) = oscillator {
+            topology.disconnect_from_output(oscillator);
+            topology
+                .connect(oscillator, envelope, AudioInputIndex(0), 0.5)
+                .unwrap();
+        }
+
+        let patch = Patch::from_topology(&topology).unwrap();
+        assert_eq!(patch.components[1].name, "envelope1");
+        assert!(!patch.components[0].output);
+        assert_eq!(patch.modulations.len(), 2);
+
+        let reloaded = Patch::from_toml(&patch.to_toml().unwrap()).unwrap();
+        assert_eq!(reloaded, patch);
+        let rebuilt = reloaded.create_topology().unwrap();
+        assert_eq!(Patch::from_topology(&rebuilt).unwrap(), patch);
+    }
+
+    #[test]
+    fn reports_unknown_types_and_out_of_range_values() {
+        let header = "[engine]\nsampling_rate = 48000\nmodulation_rate = 100\nchannels = 2\nmax_samples_per_step = 128\n";
+
+        let unknown_type = format!(
+            "{}[[components]]\nname = \"x\"\ntype = \"theremin\"\n",
+            header
+        );
+        assert_eq!(
+            Patch::from_toml(&unknown_type)
+                .unwrap()
+                .create_topology()
+                .err(),
+            Some(Error::Patch(PatchError::UnknownComponentType(
+                "theremin".to_string()
+            )))
+        );
+
+        let out_of_range = format!(
+            "{}[[components]]\nname = \"osc1\"\ntype = \"oscillator\"\nparameters = {{ level = 2.0 }}\n",
+            header
+        );
+        assert_eq!(
+            Patch::from_toml(&out_of_range)
+                .unwrap()
+                .create_topology()
+                .err(),
+            Some(Error::Patch(PatchError::ParameterOutOfRange {
+                path: "osc1.level".to_string(),
+                value: 2.0,
+                minimum_value: 0.0,
+                maximum_value: 1.0
+            }))
+        );
+
+        let unknown_parameter = format!(
+            "{}[[components]]\nname = \"osc1\"\ntype = \"oscillator\"\nparameters = {{ pitch = 2.0 }}\n",
+            header
+        );
+        assert_eq!(
+            Patch::from_toml(&unknown_parameter)
+                .unwrap()
+                .create_topology()
+                .err(),
+            Some(Error::Patch(PatchError::UnknownParameter(
+                "osc1.pitch".to_string()
+            )))
+        );
+
+        assert!(matches!(
+            Patch::from_toml("[engine]\nsampling_rate = \"fast\""),
+            Err(Error::Patch(PatchError::Syntax(_)))
+        ));
+    }
+
+    #[test]
+    fn centres_octave_modulations_on_the_parameter_value() {
+        let mut modulators = ModulationComponentsStore::default();
+        let modulator = modulators.add_component(Box::new(AlternatingModulator::new(1.0)));
+        let modulation = ModulationPatch {
+            parameter: "osc1.frequency".to_string(),
+            modulator: "lfo1".to_string(),
+            level: 1.0,
+            polarity: Polarity::Bipolar,
+            curve: ModulationCurve::Linear,
+            unit: ModulationUnit::Octaves,
+            offset: None,
+        };
+
+        let mut parameter = Parameter::new(440.0, 20.0, 20000.0);
+        let mapping = modulation.mapping(&parameter);
+        parameter
+            .add_mapped_modulation(modulator, modulation.level, mapping)
+            .unwrap();
+        parameter
+            .apply_modulations(&modulators, AudioSampleIndex(0))
+            .unwrap();
+
+        assert_eq!(mapping.offset, 0.0);
+        assert_eq!(parameter.final_value(), 880.0);
+    }
+
+    fn engine_patch() -> Patch {
+        Patch::from_toml(
+            "[engine]\nsampling_rate = 48000\nmodulation_rate = 100\nchannels = 2\nmax_samples_per_step = 128\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rejects_zero_modulation_rates() {
+        let mut patch = engine_patch();
+        patch.engine.modulation_rate = 0;
+
+        assert_eq!(
+            patch.create_engine().err(),
+            Some(Error::InvalidEngine {
+                field: "modulation_rate",
+                value: 0
+            })
+        );
+
+        patch.engine.modulation_rate = 96000;
+        assert!(patch.create_topology().is_err());
+    }
+
+    #[test]
+    fn rejects_zero_step_sizes() {
+        let mut patch = engine_patch();
+        patch.engine.max_samples_per_step = 0;
+
+        assert_eq!(
+            patch.create_engine().err(),
+            Some(Error::InvalidEngine {
+                field: "max_samples_per_step",
+                value: 0
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_zero_channels() {
+        let mut patch = engine_patch();
+        patch.engine.channels = 0;
+
+        assert_eq!(
+            patch.create_topology().err(),
+            Some(Error::InvalidEngine {
+                field: "channels",
+                value: 0
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_too_many_modulations() {
+        let mut text = format!(
+            "[engine]\nsampling_rate = 48000\nmodulation_rate = 100\nchannels = 2\nmax_samples_per_step = 128\n{}{}",
+            "[[modulators]]\nname = \"lfo1\"\ntype = \"lfo\"\n",
+            "[[components]]\nname = \"osc1\"\ntype = \"oscillator\"\n",
+        );
+        for _ in 0..=MODULATION_CAPACITY {
+            text.push_str(
+                "[[modulations]]\nparameter = \"osc1.level\"\nmodulator = \"lfo1\"\nlevel = 0.1\n",
+            );
+        }
+
+        assert_eq!(
+            Patch::from_toml(&text).unwrap().create_topology().err(),
+            Some(Error::Patch(PatchError::TooManyModulations(
+                "osc1.level".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn refuses_to_save_unsupported_components() {
+        let (engine, mut topology) = create_demo_engine().unwrap();
+        topology.add_component(ConstantGenerator::default());
+        drop(engine);
+
+        assert_eq!(
+            Patch::from_topology(&topology).err(),
+            Some(Error::Patch(PatchError::UnsupportedComponent))
+        );
+    }
+}
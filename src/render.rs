@@ -0,0 +1,314 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::core::{AudioTopology, Engine, SamplingRate};
+use crate::error::Result;
+use crate::midi::MidiPlayer;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RenderLength {
+    Fixed(Duration),
+    // Stops once every channel stayed within the threshold for `hold`, or at `limit` for patches
+    // that never go quiet.
+    UntilSilence {
+        threshold: f32,
+        hold: Duration,
+        limit: Duration,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WavFormat {
+    Float32,
+    Int16,
+    Int24,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RenderProgress {
+    pub rendered_samples: u64,
+    // The limit when rendering until silence.
+    pub total_samples: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RenderOutcome {
+    Completed { samples: u64 },
+    Cancelled { samples: u64 },
+}
+
+pub struct RenderOptions<'a> {
+    pub length: RenderLength,
+    pub format: WavFormat,
+    pub cancel: Option<&'a AtomicBool>,
+    pub progress: Option<&'a mut dyn FnMut(RenderProgress)>,
+    // Events of the file are played from sample 0. Rendering always covers the whole file.
+    pub player: Option<&'a mut MidiPlayer>,
+}
+
+impl<'a> RenderOptions<'a> {
+    pub fn new(length: RenderLength) -> Self {
+        Self {
+            length,
+            format: WavFormat::Float32,
+            cancel: None,
+            progress: None,
+            player: None,
+        }
+    }
+}
+
+// Renders in chunks of `max_samples_per_step`, as fast as the engine allows, handing each chunk
+// of interleaved samples to `output`.
+pub fn render(
+    engine: &mut Engine,
+    topology: &mut AudioTopology,
+    mut options: RenderOptions,
+    output: &mut dyn FnMut(&[f32]) -> Result<()>,
+) -> Result<RenderOutcome> {
+    let samples_per_call = engine.spec.max_samples_per_step;
+    let channels = engine.spec.channels.0 as usize;
+    let sampling_rate = engine.spec.sampling_rate;
+    let minimum_samples = options.player.as_ref().map_or(0, |p| p.end().0);
+
+    let (total_samples, silence) = match options.length {
+        RenderLength::Fixed(duration) => (duration_in_samples(duration, sampling_rate), None),
+        RenderLength::UntilSilence {
+            threshold,
+            hold,
+            limit,
+        } => (
+            duration_in_samples(limit, sampling_rate),
+            Some((threshold, duration_in_samples(hold, sampling_rate))),
+        ),
+    };
+    let total_samples = total_samples.max(minimum_samples);
+    let mut buffer = vec![0.0; samples_per_call * channels];
+
+    let mut rendered_samples = 0;
+    let mut silent_samples = 0;
+    while rendered_samples < total_samples {
+        if options.cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+            return Ok(RenderOutcome::Cancelled {
+                samples: rendered_samples,
+            });
+        }
+
+        let samples_in_loop = samples_per_call.min((total_samples - rendered_samples) as usize);
+        let buffer_slice = &mut buffer[0..samples_in_loop * channels];
+
+        match options.player.as_mut() {
+            Some(player) => player.advance(engine, topology, buffer_slice),
+            None => engine.advance(topology, buffer_slice),
+        }
+        output(buffer_slice)?;
+        rendered_samples += samples_in_loop as u64;
+
+        if let Some(progress) = options.progress.as_mut() {
+            progress(RenderProgress {
+                rendered_samples,
+                total_samples,
+            });
+        }
+
+        if let Some((threshold, hold)) = silence {
+            for frame in buffer_slice.chunks(channels) {
+                match frame.iter().all(|s| s.abs() <= threshold) {
+                    true => silent_samples += 1,
+                    false => silent_samples = 0,
+                }
+            }
+            if silent_samples >= hold && rendered_samples >= minimum_samples {
+                break;
+            }
+        }
+    }
+
+    Ok(RenderOutcome::Completed {
+        samples: rendered_samples,
+    })
+}
+
+// A cancelled render still leaves a valid file with what was rendered so far.
+pub fn render_to_wav(
+    engine: &mut Engine,
+    topology: &mut AudioTopology,
+    options: RenderOptions,
+    path: &Path,
+) -> Result<RenderOutcome> {
+    let format = options.format;
+    let (bits_per_sample, sample_format) = match format {
+        WavFormat::Float32 => (32, hound::SampleFormat::Float),
+        WavFormat::Int16 => (16, hound::SampleFormat::Int),
+        WavFormat::Int24 => (24, hound::SampleFormat::Int),
+    };
+    let spec = hound::WavSpec {
+        channels: engine.spec.channels.0,
+        sample_rate: engine.spec.sampling_rate.0,
+        bits_per_sample,
+        sample_format,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+
+    let outcome = render(engine, topology, options, &mut |samples| {
+        for s in samples {
+            match format {
+                WavFormat::Float32 => writer.write_sample(*s)?,
+                WavFormat::Int16 => writer.write_sample(to_integer(*s, 16) as i16)?,
+                WavFormat::Int24 => writer.write_sample(to_integer(*s, 24))?,
+            }
+        }
+        Ok(())
+    })?;
+    writer.finalize()?;
+
+    Ok(outcome)
+}
+
+pub(crate) fn duration_in_samples(duration: Duration, sampling_rate: SamplingRate) -> u64 {
+    (duration.as_secs_f64() * sampling_rate.0 as f64).round() as u64
+}
+
+fn to_integer(sample: f32, bits: u32) -> i32 {
+    let maximum = ((1 << (bits - 1)) - 1) as f32;
+    (sample.clamp(-1.0, 1.0) * maximum).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{empty_engine, Channels, ModulationRate};
+    use crate::midi::{MidiFile, MidiFileEvent, MidiMapping, MidiMessage};
+    use crate::testing::{ConstantGenerator, NoteGenerator};
+
+    fn render_samples(
+        engine: &mut Engine,
+        topology: &mut AudioTopology,
+        options: RenderOptions,
+    ) -> (RenderOutcome, Vec<f32>) {
+        let mut samples = vec![];
+        let outcome = render(engine, topology, options, &mut |chunk| {
+            samples.extend_from_slice(chunk);
+            Ok(())
+        })
+        .unwrap();
+        (outcome, samples)
+    }
+
+    #[test]
+    fn renders_fixed_length_in_engine_chunks() {
+        let (mut engine, mut topology) =
+            empty_engine(SamplingRate(1000), ModulationRate(100), 64, Channels(2));
+        topology.add_component(ConstantGenerator::default());
+
+        let mut chunks = vec![];
+        let outcome = render(
+            &mut engine,
+            &mut topology,
+            RenderOptions::new(RenderLength::Fixed(Duration::from_millis(150))),
+            &mut |chunk| {
+                chunks.push(chunk.len());
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(outcome, RenderOutcome::Completed { samples: 150 });
+        assert_eq!(chunks, vec![128, 128, 44]);
+    }
+
+    #[test]
+    fn renders_until_silence_after_the_last_note() {
+        let (mut engine, mut topology) =
+            empty_engine(SamplingRate(1000), ModulationRate(100), 64, Channels(1));
+        let generator_id = topology.add_component(NoteGenerator::default());
+
+        let note_on = MidiMessage::NoteOn {
+            channel: 0,
+            note: 50,
+            velocity: 127,
+        };
+        let note_off = MidiMessage::NoteOff {
+            channel: 0,
+            note: 50,
+            velocity: 0,
+        };
+        let file = MidiFile {
+            events: vec![
+                MidiFileEvent {
+                    time: Duration::from_millis(100),
+                    message: note_on,
+                },
+                MidiFileEvent {
+                    time: Duration::from_millis(200),
+                    message: note_off,
+                },
+            ],
+            duration: Duration::from_millis(200),
+        };
+        let mut player = MidiPlayer::new(
+            &file,
+            engine.spec.sampling_rate,
+            MidiMapping::new(generator_id),
+        );
+
+        let mut options = RenderOptions::new(RenderLength::UntilSilence {
+            threshold: 0.0,
+            hold: Duration::from_millis(50),
+            limit: Duration::from_secs(10),
+        });
+        options.player = Some(&mut player);
+        let (outcome, samples) = render_samples(&mut engine, &mut topology, options);
+
+        // Silence before the first note doesn't end the render, and the check happens per chunk.
+        assert_eq!(outcome, RenderOutcome::Completed { samples: 256 });
+        assert_eq!(samples[150], 0.5);
+        assert_eq!(samples[200], 0.0);
+    }
+
+    #[test]
+    fn reports_progress_and_cancels() {
+        let (mut engine, mut topology) =
+            empty_engine(SamplingRate(1000), ModulationRate(100), 64, Channels(1));
+        let cancel = AtomicBool::new(false);
+
+        let mut reported = vec![];
+        let mut progress = |p: RenderProgress| {
+            reported.push(p.rendered_samples);
+            if p.rendered_samples >= 128 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        };
+        let mut options = RenderOptions::new(RenderLength::Fixed(Duration::from_secs(1)));
+        options.cancel = Some(&cancel);
+        options.progress = Some(&mut progress);
+        let (outcome, samples) = render_samples(&mut engine, &mut topology, options);
+
+        assert_eq!(outcome, RenderOutcome::Cancelled { samples: 128 });
+        assert_eq!(samples.len(), 128);
+        assert_eq!(reported, vec![64, 128]);
+    }
+
+    #[test]
+    fn writes_integer_wav_files() {
+        let (mut engine, mut topology) =
+            empty_engine(SamplingRate(1000), ModulationRate(100), 64, Channels(2));
+        let mut generator = ConstantGenerator::default();
+        generator.level.set_value(0.5);
+        topology.add_component(generator);
+
+        let path = std::env::temp_dir().join(format!("rynth_render_{}.wav", std::process::id()));
+        let mut options = RenderOptions::new(RenderLength::Fixed(Duration::from_millis(100)));
+        options.format = WavFormat::Int16;
+        render_to_wav(&mut engine, &mut topology, options, &path).unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 16);
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.duration(), 100);
+        let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert!(samples.iter().all(|s| *s == 16384));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,7 +1,11 @@
-use crate::core::concepts::{AudioSampleIndex, ModulationSampleIndex};
+use crate::core::buffers::MultiChannelSliceMut;
+use crate::core::concepts::{AudioSampleIndex, Channels, ModulationSampleIndex};
 use crate::core::parameter::Parameter;
+use crate::core::reflection::{ParameterDescriptor, ParameterUnit, Parameterized};
 use crate::core::topology::ModulationComponentsStore;
-use crate::core::traits::{AudioComponent, ModulationComponent};
+use crate::core::routing::AudioInputIndex;
+use crate::core::traits::{AudioComponent, AudioInputs, ModulationComponent};
+use crate::error::Result;
 use std::ops::Range;
 
 pub struct ConstantGenerator {
@@ -16,15 +20,198 @@ impl Default for ConstantGenerator {
     }
 }
 
+const CONSTANT_GENERATOR_PARAMETERS: &[ParameterDescriptor] = &[
+    ParameterDescriptor {
+        name: "level",
+        unit: ParameterUnit::None,
+    },
+];
+
+impl Parameterized for ConstantGenerator {
+    fn parameter_descriptors(&self) -> &'static [ParameterDescriptor] {
+        CONSTANT_GENERATOR_PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<&Parameter> {
+        match index {
+            0 => Some(&self.level),
+            _ => None,
+        }
+    }
+
+    fn parameter_mut(&mut self, index: usize) -> Option<&mut Parameter> {
+        match index {
+            0 => Some(&mut self.level),
+            _ => None,
+        }
+    }
+}
+
 impl AudioComponent for ConstantGenerator {
-    fn process_audio(&mut self, data: &mut [f32], _: Range<AudioSampleIndex>) {
-        for sample in data.iter_mut() {
-            *sample = self.level.final_value();
+    fn process_audio(
+        &mut self,
+        _: &AudioInputs,
+        output: &mut MultiChannelSliceMut,
+        sample_range: Range<AudioSampleIndex>,
+    ) {
+        let start = sample_range.start.0;
+        for (i, sample) in output.channel_mut(0).iter_mut().enumerate() {
+            *sample = self.level.value_at(AudioSampleIndex(start + i as u64));
+        }
+    }
+
+    fn apply_modulations(
+        &mut self,
+        modulators: &ModulationComponentsStore,
+        sample: AudioSampleIndex,
+    ) -> Result<()> {
+        self.level.apply_modulations(modulators, sample)
+    }
+
+    fn apply_automation(&mut self, sample: AudioSampleIndex) {
+        self.level.apply_automation(sample);
+    }
+
+    fn next_automation_breakpoint(&self, sample: AudioSampleIndex) -> Option<AudioSampleIndex> {
+        self.level.next_automation_breakpoint(sample)
+    }
+}
+
+pub struct ConstantStereoGenerator {
+    pub left: Parameter,
+    pub right: Parameter,
+}
+
+impl Default for ConstantStereoGenerator {
+    fn default() -> Self {
+        Self {
+            left: Parameter::new(1.0, -1.0, 1.0),
+            right: Parameter::new(1.0, -1.0, 1.0),
+        }
+    }
+}
+
+const CONSTANT_STEREO_GENERATOR_PARAMETERS: &[ParameterDescriptor] = &[
+    ParameterDescriptor {
+        name: "left",
+        unit: ParameterUnit::None,
+    },
+    ParameterDescriptor {
+        name: "right",
+        unit: ParameterUnit::None,
+    },
+];
+
+impl Parameterized for ConstantStereoGenerator {
+    fn parameter_descriptors(&self) -> &'static [ParameterDescriptor] {
+        CONSTANT_STEREO_GENERATOR_PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<&Parameter> {
+        match index {
+            0 => Some(&self.left),
+            1 => Some(&self.right),
+            _ => None,
+        }
+    }
+
+    fn parameter_mut(&mut self, index: usize) -> Option<&mut Parameter> {
+        match index {
+            0 => Some(&mut self.left),
+            1 => Some(&mut self.right),
+            _ => None,
+        }
+    }
+}
+
+impl AudioComponent for ConstantStereoGenerator {
+    fn channels(&self) -> Channels {
+        Channels(2)
+    }
+
+    fn process_audio(
+        &mut self,
+        _: &AudioInputs,
+        output: &mut MultiChannelSliceMut,
+        _: Range<AudioSampleIndex>,
+    ) {
+        output.channel_mut(0).fill(self.left.final_value());
+        output.channel_mut(1).fill(self.right.final_value());
+    }
+
+    fn apply_modulations(
+        &mut self,
+        modulators: &ModulationComponentsStore,
+        sample: AudioSampleIndex,
+    ) -> Result<()> {
+        let left = self.left.apply_modulations(modulators, sample);
+        let right = self.right.apply_modulations(modulators, sample);
+        left.and(right)
+    }
+}
+
+pub struct ScalingEffect {
+    pub level: Parameter,
+}
+
+impl Default for ScalingEffect {
+    fn default() -> Self {
+        Self {
+            level: Parameter::new(1.0, -1.0, 1.0),
+        }
+    }
+}
+
+const SCALING_EFFECT_PARAMETERS: &[ParameterDescriptor] = &[
+    ParameterDescriptor {
+        name: "level",
+        unit: ParameterUnit::None,
+    },
+];
+
+impl Parameterized for ScalingEffect {
+    fn parameter_descriptors(&self) -> &'static [ParameterDescriptor] {
+        SCALING_EFFECT_PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<&Parameter> {
+        match index {
+            0 => Some(&self.level),
+            _ => None,
+        }
+    }
+
+    fn parameter_mut(&mut self, index: usize) -> Option<&mut Parameter> {
+        match index {
+            0 => Some(&mut self.level),
+            _ => None,
+        }
+    }
+}
+
+impl AudioComponent for ScalingEffect {
+    fn number_of_inputs(&self) -> usize {
+        1
+    }
+
+    fn process_audio(
+        &mut self,
+        inputs: &AudioInputs,
+        output: &mut MultiChannelSliceMut,
+        _: Range<AudioSampleIndex>,
+    ) {
+        let input = inputs.get(AudioInputIndex(0)).unwrap();
+        for (sample, input) in output.channel_mut(0).iter_mut().zip(input.channel(0)) {
+            *sample = *input * self.level.final_value();
         }
     }
 
-    fn apply_modulations(&mut self, modulators: &ModulationComponentsStore, _: AudioSampleIndex) {
-        self.level.apply_modulations(modulators);
+    fn apply_modulations(
+        &mut self,
+        modulators: &ModulationComponentsStore,
+        sample: AudioSampleIndex,
+    ) -> Result<()> {
+        self.level.apply_modulations(modulators, sample)
     }
 }
 
@@ -38,6 +225,8 @@ impl AlternatingModulator {
     }
 }
 
+impl Parameterized for AlternatingModulator {}
+
 impl ModulationComponent for AlternatingModulator {
 
     fn process_modulation(&mut self, _sample: ModulationSampleIndex) {
@@ -49,22 +238,113 @@ impl ModulationComponent for AlternatingModulator {
     }
 }
 
+pub struct FollowingModulator {
+    pub level: Parameter,
+}
+
+impl Default for FollowingModulator {
+    fn default() -> Self {
+        Self {
+            level: Parameter::new(0.0, -1.0, 1.0),
+        }
+    }
+}
+
+const FOLLOWING_MODULATOR_PARAMETERS: &[ParameterDescriptor] = &[
+    ParameterDescriptor {
+        name: "level",
+        unit: ParameterUnit::None,
+    },
+];
+
+impl Parameterized for FollowingModulator {
+    fn parameter_descriptors(&self) -> &'static [ParameterDescriptor] {
+        FOLLOWING_MODULATOR_PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<&Parameter> {
+        match index {
+            0 => Some(&self.level),
+            _ => None,
+        }
+    }
+
+    fn parameter_mut(&mut self, index: usize) -> Option<&mut Parameter> {
+        match index {
+            0 => Some(&mut self.level),
+            _ => None,
+        }
+    }
+}
+
+impl ModulationComponent for FollowingModulator {
+    fn process_modulation(&mut self, _sample: ModulationSampleIndex) {}
+
+    fn get_current_level(&self) -> f32 {
+        self.level.final_value()
+    }
+
+    fn apply_modulations(
+        &mut self,
+        modulators: &ModulationComponentsStore,
+        sample: AudioSampleIndex,
+    ) -> Result<()> {
+        self.level.apply_modulations(modulators, sample)
+    }
+}
+
+// Outputs the held note divided by 100, scaled by velocity.
+#[derive(Default)]
+pub struct NoteGenerator {
+    pub level: f32,
+}
+
+impl Parameterized for NoteGenerator {}
+
+impl AudioComponent for NoteGenerator {
+    fn process_audio(
+        &mut self,
+        _: &AudioInputs,
+        output: &mut MultiChannelSliceMut,
+        _: Range<AudioSampleIndex>,
+    ) {
+        output.channel_mut(0).fill(self.level);
+    }
+
+    fn apply_modulations(
+        &mut self,
+        _: &ModulationComponentsStore,
+        _: AudioSampleIndex,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn note_on(&mut self, note: u8, velocity: u8, _sample: AudioSampleIndex) {
+        self.level = note as f32 / 100.0 * velocity as f32 / 127.0;
+    }
+
+    fn note_off(&mut self, _note: u8, _sample: AudioSampleIndex) {
+        self.level = 0.0;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::{AudioComponent, AudioSampleIndex};
+    use crate::core::{AudioComponent, AudioSampleIndex, MultiChannelBuffer};
 
     #[test]
     fn generates_constant_level() {
         let test_samples = 8000;
-        let mut obtained = vec![0.0; test_samples];
+        let mut buffer = MultiChannelBuffer::new(Channels(1), test_samples);
         let expected_value = 0.6;
 
         let mut generator = ConstantGenerator::default();
         generator.level.set_value(expected_value);
 
         let range = AudioSampleIndex(0)..AudioSampleIndex((test_samples - 1) as u64);
-        generator.process_audio(obtained.as_mut_slice(), range);
+        generator.process_audio(&AudioInputs::empty(), &mut buffer.slice_mut(0..test_samples), range);
+        let obtained = buffer.slice(0..test_samples).channel(0).to_vec();
 
         assert_eq!(obtained, vec![expected_value; test_samples]);
     }
@@ -76,9 +356,10 @@ mod tests {
         let mut generator = ConstantGenerator::default();
         generator.level.set_value(0.5);
 
-        let mut obtained = vec![f32::NAN; test_samples as usize];
-        let range = AudioSampleIndex(0)..AudioSampleIndex(test_samples as u64);
-        generator.process_audio(obtained.as_mut_slice(), range);
+        let mut buffer = MultiChannelBuffer::new(Channels(1), test_samples as usize);
+        let range = AudioSampleIndex(0)..AudioSampleIndex(test_samples);
+        generator.process_audio(&AudioInputs::empty(), &mut buffer.slice_mut(0..test_samples as usize), range);
+        let obtained = buffer.slice(0..test_samples as usize).channel(0).to_vec();
 
         assert_eq!(obtained, vec![0.5; test_samples as usize]);
 
@@ -86,11 +367,11 @@ mod tests {
         let constant_modulator_id = modulators.add_component(Box::new(AlternatingModulator::new(0.5)));
 
         generator.level.add_modulation(constant_modulator_id, 1.0);
-        generator.apply_modulations(&modulators, AudioSampleIndex(8000));
+        generator.apply_modulations(&modulators, AudioSampleIndex(8000)).unwrap();
 
-        let mut obtained = vec![f32::NAN; test_samples as usize];
         let range = AudioSampleIndex(test_samples)..AudioSampleIndex(test_samples * 2);
-        generator.process_audio(obtained.as_mut_slice(), range);
+        generator.process_audio(&AudioInputs::empty(), &mut buffer.slice_mut(0..test_samples as usize), range);
+        let obtained = buffer.slice(0..test_samples as usize).channel(0).to_vec();
 
         assert_eq!(obtained, vec![1.0; test_samples as usize]);
     }
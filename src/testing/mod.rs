@@ -1,3 +1,5 @@
 mod constant_components;
+mod simulated_backend;
 
-pub use crate::testing::constant_components::*;
\ No newline at end of file
+pub use crate::testing::constant_components::*;
+pub use crate::testing::simulated_backend::*;
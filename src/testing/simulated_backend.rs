@@ -0,0 +1,118 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::app::{AudioBackend, AudioCallback};
+use crate::core::{Channels, EngineSpec};
+use crate::error::{Error, Result};
+
+// A device that calls back with a scripted sequence of buffer sizes and records everything it
+// receives. It stops at the end of the script, when the stop sender hangs up, or when the
+// callback panics.
+pub struct SimulatedBackend {
+    // Frames per callback.
+    pub buffer_sizes: Vec<usize>,
+    // The device channel count, set to the engine's when missing. Like real backends, it
+    // refuses to run an engine with a different count.
+    pub channels: Option<Channels>,
+    // Sleeps before each callback, cycled through. Empty for no delays.
+    pub delays: Vec<Duration>,
+    // Runs on the backend thread right before the callback with the given index, e.g. to send
+    // commands at an exact point of the stream.
+    pub before_callback: Option<Box<dyn FnMut(usize) + Send>>,
+    pub recorded: Vec<f32>,
+    pub callbacks: usize,
+    pub panic: Option<String>,
+}
+
+impl SimulatedBackend {
+    pub fn new(buffer_sizes: Vec<usize>) -> Self {
+        Self {
+            buffer_sizes,
+            channels: None,
+            delays: vec![],
+            before_callback: None,
+            recorded: vec![],
+            callbacks: 0,
+            panic: None,
+        }
+    }
+
+    pub fn with_channels(self, channels: Channels) -> Self {
+        Self {
+            channels: Some(channels),
+            ..self
+        }
+    }
+
+    pub fn with_delays(self, delays: Vec<Duration>) -> Self {
+        Self { delays, ..self }
+    }
+
+    pub fn with_hook<F: FnMut(usize) + Send + 'static>(self, hook: F) -> Self {
+        Self {
+            before_callback: Some(Box::new(hook)),
+            ..self
+        }
+    }
+
+    pub fn recorded_channel(&self, channel: usize) -> Vec<f32> {
+        let channels = self.channels.map_or(1, |c| c.0 as usize);
+        self.recorded
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .copied()
+            .collect()
+    }
+}
+
+impl AudioBackend for SimulatedBackend {
+    fn run(
+        &mut self,
+        spec: &EngineSpec,
+        mut callback: AudioCallback,
+        stop: Receiver<()>,
+    ) -> Result<()> {
+        let channels = *self.channels.get_or_insert(spec.channels);
+        if channels.0 != spec.channels.0 {
+            return Err(Error::Device(format!(
+                "the device has {} channels but the engine renders {}",
+                channels.0, spec.channels.0
+            )));
+        }
+        let channels = channels.0 as usize;
+        let largest = self.buffer_sizes.iter().copied().max().unwrap_or(0);
+        let mut buffer = vec![0.0; largest * channels];
+
+        for (index, frames) in self.buffer_sizes.iter().enumerate() {
+            if let Err(TryRecvError::Disconnected) = stop.try_recv() {
+                break;
+            }
+            if !self.delays.is_empty() {
+                thread::sleep(self.delays[index % self.delays.len()]);
+            }
+            if let Some(hook) = self.before_callback.as_mut() {
+                hook(index);
+            }
+
+            let data = &mut buffer[..frames * channels];
+            data.fill(f32::NAN);
+            if let Err(payload) = catch_unwind(AssertUnwindSafe(|| callback(data))) {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "callback panicked".to_string());
+                self.panic = Some(message);
+                break;
+            }
+
+            self.recorded.extend_from_slice(data);
+            self.callbacks += 1;
+        }
+
+        Ok(())
+    }
+}
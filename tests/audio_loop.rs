@@ -0,0 +1,173 @@
+use rynth::app::audio_loop;
+use rynth::components::{LowFrequencyOscillator, Oscillator};
+use rynth::core::{
+    command_queue, empty_engine, AudioTopology, Channels, Command, Engine, ModulationRate,
+    SamplingRate,
+};
+use rynth::render::{render, RenderLength, RenderOptions};
+use rynth::testing::{ConstantGenerator, SimulatedBackend};
+use rynth::Error;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn modulated_oscillator(channels: Channels) -> (Engine, AudioTopology) {
+    let (engine, mut topology) =
+        empty_engine(SamplingRate(48000), ModulationRate(100), 128, channels);
+
+    let modulator_id = topology
+        .add_modulator(LowFrequencyOscillator::new(
+            5.0,
+            engine.spec.modulation_rate,
+        ))
+        .unwrap();
+    let mut oscillator = Oscillator::new(440.0, engine.spec.sampling_rate);
+    oscillator.level.set_value(0.5);
+    oscillator.level.add_modulation(modulator_id, 0.3);
+    topology.add_component(oscillator);
+
+    (engine, topology)
+}
+
+fn render_offline(channels: Channels, frames: usize) -> Vec<f32> {
+    let (mut engine, mut topology) = modulated_oscillator(channels);
+    let duration = Duration::from_secs_f64(frames as f64 / engine.spec.sampling_rate.0 as f64);
+
+    let mut output = vec![];
+    render(
+        &mut engine,
+        &mut topology,
+        RenderOptions::new(RenderLength::Fixed(duration)),
+        &mut |samples| {
+            output.extend_from_slice(samples);
+            Ok(())
+        },
+    )
+    .unwrap();
+    output
+}
+
+fn play(backend: &mut SimulatedBackend, engine: Engine, topology: AudioTopology) {
+    let (_stop, stopped) = channel();
+    audio_loop(engine, topology, backend, stopped).unwrap();
+}
+
+#[test]
+fn variable_callback_sizes_match_offline_render() {
+    let sizes = vec![128, 1, 37, 128, 64, 5, 127, 2];
+    let frames = sizes.iter().sum();
+    let (engine, topology) = modulated_oscillator(Channels(2));
+
+    let mut backend = SimulatedBackend::new(sizes);
+    play(&mut backend, engine, topology);
+
+    assert_eq!(backend.panic, None);
+    assert_eq!(backend.callbacks, 8);
+    assert_eq!(backend.recorded, render_offline(Channels(2), frames));
+}
+
+#[test]
+fn odd_channel_counts_are_interleaved() {
+    let (engine, topology) = modulated_oscillator(Channels(3));
+
+    let mut backend = SimulatedBackend::new(vec![100, 28, 77]);
+    play(&mut backend, engine, topology);
+
+    let expected = render_offline(Channels(3), 205);
+    assert_eq!(backend.recorded, expected);
+    assert_eq!(backend.recorded_channel(0), backend.recorded_channel(2));
+    assert_eq!(backend.recorded_channel(1).len(), 205);
+}
+
+#[test]
+fn jittered_callbacks_produce_the_same_output() {
+    let (engine, topology) = modulated_oscillator(Channels(1));
+
+    let mut backend = SimulatedBackend::new(vec![64, 128, 16, 128]).with_delays(vec![
+        Duration::from_millis(0),
+        Duration::from_millis(3),
+        Duration::from_micros(500),
+    ]);
+    play(&mut backend, engine, topology);
+
+    assert_eq!(backend.recorded, render_offline(Channels(1), 336));
+}
+
+#[test]
+fn applies_commands_at_callback_boundaries_and_reports_errors() {
+    let (mut engine, mut topology) =
+        empty_engine(SamplingRate(48000), ModulationRate(100), 128, Channels(1));
+    let generator_id = topology.add_component(ConstantGenerator::default());
+    topology.set_name(generator_id, "gen1");
+    let level = topology.find_parameter("gen1.level").unwrap();
+
+    let (commands, receiver) = command_queue(16);
+    engine.set_command_receiver(receiver);
+    let commands = Arc::new(Mutex::new(commands));
+
+    let hook_commands = commands.clone();
+    let mut backend = SimulatedBackend::new(vec![50, 50, 50]).with_hook(move |index| {
+        let mut commands = hook_commands.lock().unwrap();
+        match index {
+            1 => commands.send(Command::SetParameter {
+                parameter: level,
+                value: 0.5,
+            }),
+            2 => commands.send(Command::SetParameter {
+                parameter: level,
+                value: 2.0,
+            }),
+            _ => Ok(()),
+        }
+        .unwrap();
+    });
+    play(&mut backend, engine, topology);
+
+    let default_level = ConstantGenerator::default().level.get_value();
+    let mut expected = vec![default_level; 50];
+    expected.extend(vec![0.5; 100]);
+    assert_eq!(backend.recorded, expected);
+
+    assert!(matches!(
+        commands.lock().unwrap().pop_error(),
+        Some(Error::ParameterOutOfRange { value, .. }) if value == 2.0
+    ));
+}
+
+#[test]
+fn stops_when_the_stop_sender_hangs_up() {
+    let (engine, topology) = modulated_oscillator(Channels(1));
+    let mut backend = SimulatedBackend::new(vec![128; 10]);
+
+    let (stop, stopped) = channel();
+    drop(stop);
+    audio_loop(engine, topology, &mut backend, stopped).unwrap();
+
+    assert_eq!(backend.callbacks, 0);
+}
+
+#[test]
+fn handles_oversized_callbacks() {
+    let sizes = vec![128, 256, 1000, 129];
+    let frames = sizes.iter().sum();
+    let (engine, topology) = modulated_oscillator(Channels(2));
+
+    let mut backend = SimulatedBackend::new(sizes);
+    play(&mut backend, engine, topology);
+
+    assert_eq!(backend.panic, None);
+    assert_eq!(backend.callbacks, 4);
+    assert_eq!(backend.recorded, render_offline(Channels(2), frames));
+}
+
+#[test]
+fn rejects_devices_with_other_channel_counts() {
+    let (engine, topology) = modulated_oscillator(Channels(2));
+    let mut backend = SimulatedBackend::new(vec![128; 4]).with_channels(Channels(1));
+
+    let (_stop, stopped) = channel();
+    let result = audio_loop(engine, topology, &mut backend, stopped);
+
+    assert!(matches!(result, Err(Error::Device(_))));
+    assert_eq!(backend.callbacks, 0);
+}
@@ -1,5 +1,6 @@
 use anyhow::{bail, Result};
 use rynth::core::{AudioTopology, Engine};
+use rynth::render::{render_to_wav, RenderLength, RenderOptions};
 use std::iter::FromIterator;
 use std::path::Path;
 use std::time::Duration;
@@ -10,33 +11,8 @@ fn save_engine_result(
     duration: Duration,
     path: &Path,
 ) -> Result<()> {
-    let samples_per_call = engine.spec.max_samples_per_step;
-    let channels = engine.spec.channels;
-    let sampling_rate = engine.spec.sampling_rate;
-    let test_samples = (duration.as_secs_f32() * sampling_rate.0 as f32) as usize;
-    let mut buffer = vec![0.0; samples_per_call * channels.0 as usize];
-
-    let spec = hound::WavSpec {
-        channels: channels.0,
-        sample_rate: sampling_rate.0,
-        bits_per_sample: 32,
-        sample_format: hound::SampleFormat::Float,
-    };
-    let mut writer = hound::WavWriter::create(path, spec)?;
-
-    let mut current_sample = 0;
-    while current_sample < test_samples {
-        let samples_in_loop = samples_per_call.min(test_samples - current_sample);
-        let samples_in_buffer = samples_in_loop * channels.0 as usize;
-        let buffer_slice = &mut buffer.as_mut_slice()[0..samples_in_buffer];
-
-        engine.advance(topology, buffer_slice);
-        for s in buffer_slice.iter() {
-            writer.write_sample(*s)?;
-        }
-
-        current_sample += samples_in_loop;
-    }
+    let options = RenderOptions::new(RenderLength::Fixed(duration));
+    render_to_wav(engine, topology, options, path)?;
 
     Ok(())
 }
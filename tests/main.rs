@@ -1,3 +1,4 @@
+mod audio_loop;
 mod helpers;
 mod resource_db;
 
@@ -37,7 +38,7 @@ fn oscillator_amplitude_modulation() -> Result<()> {
     let modulator_id = topology.add_modulator(LowFrequencyOscillator::new(
         2.0,
         engine.spec.modulation_rate,
-    ));
+    ))?;
 
     let mut oscillator = Oscillator::new(500.0, engine.spec.sampling_rate);
 
@@ -63,7 +64,7 @@ fn oscillator_frequency_modulation() -> Result<()> {
     let modulator_id = topology.add_modulator(LowFrequencyOscillator::new(
         10.0,
         engine.spec.modulation_rate,
-    ));
+    ))?;
 
     let mut oscillator = Oscillator::new(1500.0, engine.spec.sampling_rate);
 
@@ -89,11 +90,11 @@ fn oscillator_frequency_and_amplitude_modulation() -> Result<()> {
     let modulator1_id = topology.add_modulator(LowFrequencyOscillator::new(
         2.0,
         engine.spec.modulation_rate,
-    ));
+    ))?;
     let modulator2_id = topology.add_modulator(LowFrequencyOscillator::new(
         10.0,
         engine.spec.modulation_rate,
-    ));
+    ))?;
 
     let mut oscillator = Oscillator::new(500.0, engine.spec.sampling_rate);
 